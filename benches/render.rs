@@ -0,0 +1,188 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use onna::{
+    buffer::BufferDiffIter,
+    color::{Ansi256, Rgb},
+    producer::copy_frame,
+    render::{DefaultRenderer, Pixel, RenderOptions},
+    Colorize, Renderer,
+};
+
+/// The system allocator, counting how many times it allocates so the benchmarks can show how many allocations
+/// something makes along with how long it takes
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// How many times `f` allocated
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+// about the size of a full screen terminal
+const WIDTH: u32 = 200;
+const HEIGHT: u32 = 60;
+
+/// A frame of noise from a small xorshift generator, so every run gets the same frames
+fn noise(seed: u32) -> Vec<Pixel> {
+    let mut state = seed.max(1);
+    (0..WIDTH * HEIGHT)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let [r, g, b, _] = state.to_le_bytes();
+            [r, g, b, 0]
+        })
+        .collect()
+}
+
+/// A copy of `frame` where one in every `period` pixels is different
+fn churn(frame: &[Pixel], period: usize) -> Vec<Pixel> {
+    let other = noise(7);
+    frame
+        .iter()
+        .zip(other)
+        .enumerate()
+        .map(|(i, (pixel, other))| if i % period == 0 { other } else { *pixel })
+        .collect()
+}
+
+/// The frames are diffed and rendered at two extremes: a mostly still video where one in 20 pixels changes, and one
+/// where every pixel changes
+fn frame_pairs() -> [(&'static str, Vec<Pixel>, Vec<Pixel>); 2] {
+    let prev = noise(3);
+    [
+        ("low churn", churn(&prev, 20), prev.clone()),
+        ("high churn", churn(&prev, 1), prev),
+    ]
+}
+
+fn diff(c: &mut Criterion) {
+    let mut group = c.benchmark_group("BufferDiffIter");
+    for (name, current, prev) in frame_pairs() {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(name),
+            &(current, prev),
+            |b, (current, prev)| {
+                b.iter(|| BufferDiffIter::new(black_box(current), black_box(prev)).count())
+            },
+        );
+    }
+    group.finish();
+}
+
+fn render_frame<C: Colorize>(c: &mut Criterion, name: &str)
+where
+    DefaultRenderer<C>: Renderer,
+{
+    let mut group = c.benchmark_group(format!("render_frame/{name}"));
+    for (churn, current, prev) in frame_pairs() {
+        let mut renderer =
+            DefaultRenderer::<C>::from_dims(WIDTH, HEIGHT, &RenderOptions::default());
+        let mut state = renderer.create_state();
+        renderer.consume(&prev);
+        renderer.consume(&current);
+        let mut out = Vec::new();
+        group.bench_function(churn, |b| {
+            b.iter(|| {
+                out.clear();
+                renderer.render_frame(&mut out, &mut state).unwrap();
+                out.len()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn render(c: &mut Criterion) {
+    render_frame::<Rgb>(c, "Rgb");
+    render_frame::<Ansi256>(c, "Ansi256");
+}
+
+fn write_escape<C: Colorize>(c: &mut Criterion, name: &str) {
+    let colors: Vec<C> = noise(5)
+        .iter()
+        .map(|[r, g, b, _]| C::from_rgb([*r, *g, *b]))
+        .collect();
+    let mut out = Vec::new();
+    c.bench_function(&format!("write_escape/{name}"), |b| {
+        b.iter(|| {
+            out.clear();
+            for color in &colors {
+                color.write_escape(&mut out).unwrap();
+            }
+            out.len()
+        })
+    });
+}
+
+fn escapes(c: &mut Criterion) {
+    write_escape::<Rgb>(c, "Rgb");
+    write_escape::<Ansi256>(c, "Ansi256");
+}
+
+/// Copies 100 decoded frames into a slot the way the producer used to with `to_vec`, and with [`copy_frame`]. The
+/// terminal shrinks for 20 of them, so the frames change size twice
+fn frame_copy(c: &mut Criterion) {
+    let full = noise(9).concat();
+    let small = &full[..full.len() / 4];
+    let frames: Vec<&[u8]> = (0..100)
+        .map(|i| if (40..60).contains(&i) { small } else { &full })
+        .collect();
+    let to_vec = |slot: &mut Vec<u8>| {
+        for frame in &frames {
+            *slot = frame.to_vec();
+            black_box(&slot);
+        }
+    };
+    let reuse = |slot: &mut Vec<u8>| {
+        for frame in &frames {
+            copy_frame(slot, frame);
+            black_box(&slot);
+        }
+    };
+    let mut group = c.benchmark_group("frame_copy");
+    group.bench_function("to_vec", |b| {
+        let mut slot = Vec::new();
+        b.iter(|| to_vec(&mut slot))
+    });
+    group.bench_function("copy_frame", |b| {
+        let mut slot = Vec::new();
+        b.iter(|| reuse(&mut slot))
+    });
+    group.finish();
+    // starting from an empty slot, so copy_frame's only allocation is the first frame
+    println!(
+        "frame_copy allocations for {} frames: to_vec {}, copy_frame {}",
+        frames.len(),
+        allocations(|| to_vec(&mut Vec::new())),
+        allocations(|| reuse(&mut Vec::new())),
+    );
+}
+
+criterion_group!(benches, diff, render, escapes, frame_copy);
+criterion_main!(benches);
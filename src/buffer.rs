@@ -1,13 +1,25 @@
-use crate::color::Colorize;
-use std::ops::Range;
-pub struct BufferDiffIter<'a, T: PartialEq + Clone> {
+use crate::color::{Colorize, Palette};
+use std::{ops::Range, sync::Arc};
+/// Yields the runs of `current` that differ from `prev` along with their value.
+/// A run ends where the value changes or where `current` matches `prev` again, so adjacent changed pixels with
+/// different values come out as separate runs, and a run can end exactly at the end of the buffer
+pub struct BufferDiffIter<'a, T: PartialEq + Clone, F = fn(&T, &T) -> bool> {
     current: &'a [T],
     prev: &'a [T],
     idx: usize,
+    same_run: F,
 }
 
 impl<'a, T: PartialEq + Clone> BufferDiffIter<'a, T> {
     pub fn new(current: &'a [T], prev: &'a [T]) -> Self {
+        Self::with_runs(current, prev, T::eq as fn(&T, &T) -> bool)
+    }
+}
+
+impl<'a, T: PartialEq + Clone, F: Fn(&T, &T) -> bool> BufferDiffIter<'a, T, F> {
+    /// Like [`new`](BufferDiffIter::new), but a run carries on through the values that `same_run` says are close
+    /// enough to its first one instead of only through equal ones. It still ends where `current` matches `prev`
+    pub fn with_runs(current: &'a [T], prev: &'a [T], same_run: F) -> Self {
         assert_eq!(
             prev.len(),
             current.len(),
@@ -17,11 +29,12 @@ impl<'a, T: PartialEq + Clone> BufferDiffIter<'a, T> {
             current,
             prev,
             idx: 0,
+            same_run,
         }
     }
 }
 
-impl<'a, T: PartialEq + Clone> Iterator for BufferDiffIter<'a, T> {
+impl<'a, T: PartialEq + Clone, F: Fn(&T, &T) -> bool> Iterator for BufferDiffIter<'a, T, F> {
     type Item = (Range<usize>, T);
     fn next(&mut self) -> Option<Self::Item> {
         while self.prev.get(self.idx)? == self.current.get(self.idx)? {
@@ -31,32 +44,178 @@ impl<'a, T: PartialEq + Clone> Iterator for BufferDiffIter<'a, T> {
         let item = self.current.get(self.idx)?;
         loop {
             match self.current.get(self.idx) {
-                Some(i) if i == item && i != &self.prev[self.idx] => self.idx += 1,
+                Some(i) if (self.same_run)(item, i) && i != &self.prev[self.idx] => self.idx += 1,
                 _ => return Some((start..self.idx, item.clone())),
             }
         }
     }
 }
 
+/// A rectangle of pixels as `(x, y, width, height)`
+pub type Rect = (usize, usize, usize, usize);
+
+/// The smallest rectangle that contains every pixel that differs between the two buffers,
+/// or `None` if they are the same
+pub fn dirty_rect<T: PartialEq + Clone>(current: &[T], prev: &[T], width: usize) -> Option<Rect> {
+    let (mut left, mut top, mut right, mut bottom) = (usize::MAX, usize::MAX, 0, 0);
+    for (range, _) in BufferDiffIter::new(current, prev) {
+        let (first_row, last_row) = (range.start / width, (range.end - 1) / width);
+        // a run that wraps onto the next row touches both edges
+        let (start_col, end_col) = if first_row == last_row {
+            (range.start % width, (range.end - 1) % width)
+        } else {
+            (0, width - 1)
+        };
+        left = left.min(start_col);
+        right = right.max(end_col);
+        top = top.min(first_row);
+        bottom = bottom.max(last_row);
+    }
+    (top != usize::MAX).then(|| (left, top, right - left + 1, bottom - top + 1))
+}
+
+/// Splits an RGBx pixel into its color, quantized to `palette` if there is one, and the character that is drawn with it
+fn split_pixel<C: Colorize>([r, g, b, chr]: [u8; 4], palette: Option<&Palette>) -> (C, u8) {
+    (C::from_rgb_in([r, g, b], palette), chr)
+}
+
 // Technically this is unneeded lmfao. This used to contain a pixel sorter, but then benchmarks showed it was too slow
 pub struct Differ<C: Colorize> {
     data: Vec<(Range<usize>, C, u8)>,
+    // diff against nothing on the next assign, so every cell comes out
+    full_redraw: bool,
+    palette: Option<Arc<Palette>>,
 }
 
 impl<C: Colorize> Differ<C> {
+    /// The first diff has every cell in it, since there's no telling what's on the screen before the first frame
     pub fn new(width: u32, height: u32) -> Self {
         Self {
             data: Vec::with_capacity(width as usize * height as usize),
+            full_redraw: true,
+            palette: None,
         }
     }
-    pub fn assign_diff(&mut self, curr: &[[u8; 4]], prev: &[[u8; 4]]) {
+    /// Quantizes the colors of the pixels to `palette` if there is one
+    pub fn with_palette(mut self, palette: Option<Arc<Palette>>) -> Self {
+        self.palette = palette;
+        self
+    }
+    /// Makes the next diff have every cell in it, as if the previous buffer were nothing like the current one
+    pub fn force_full_redraw(&mut self) {
+        self.full_redraw = true;
+    }
+    fn assign<T: PartialEq + Clone>(
+        &mut self,
+        curr: &[T],
+        prev: &[T],
+        same_run: impl Fn(&T, &T) -> bool,
+        split: impl Fn(T) -> (C, u8),
+    ) {
         self.data.clear();
-        let diff_iter = BufferDiffIter::new(curr, prev)
-            .map(|(pos, [r, g, b, chr])| (pos, C::from_rgb([r, g, b]), chr));
-
-        self.data.extend(diff_iter);
+        if std::mem::take(&mut self.full_redraw) {
+            // the runs of values, the same as diffing against a buffer where nothing matches
+            let mut start = 0;
+            while let Some(first) = curr.get(start) {
+                let len = curr[start..]
+                    .iter()
+                    .position(|value| !same_run(first, value))
+                    .unwrap_or(curr.len() - start);
+                let (color, glyph) = split(first.clone());
+                self.data.push((start..start + len, color, glyph));
+                start += len;
+            }
+        } else {
+            self.data
+                .extend(
+                    BufferDiffIter::with_runs(curr, prev, same_run).map(|(pos, value)| {
+                        let (color, glyph) = split(value);
+                        (pos, color, glyph)
+                    }),
+                );
+        }
+    }
+    pub fn assign_diff(&mut self, curr: &[[u8; 4]], prev: &[[u8; 4]]) {
+        let palette = self.palette.clone();
+        self.assign(curr, prev, PartialEq::eq, |pixel| {
+            split_pixel(pixel, palette.as_deref())
+        });
+    }
+    /// Like [`assign_diff`](Self::assign_diff), but a stride carries on through the pixels with the same character
+    /// whose color is within `tolerance` of its first one in every channel, and they're all drawn in its color.
+    /// It's lossy, but noisy video comes out in far fewer escapes
+    pub fn assign_diff_within(&mut self, curr: &[[u8; 4]], prev: &[[u8; 4]], tolerance: u8) {
+        if tolerance == 0 {
+            return self.assign_diff(curr, prev);
+        }
+        let same_run = |a: &[u8; 4], b: &[u8; 4]| {
+            a[3] == b[3] && (0..3).all(|i| a[i].abs_diff(b[i]) <= tolerance)
+        };
+        let palette = self.palette.clone();
+        self.assign(curr, prev, same_run, |pixel| {
+            split_pixel(pixel, palette.as_deref())
+        });
+    }
+    /// Diffs buffers that are already colors, for renderers that draw the same glyph in every cell
+    pub fn assign_colors(&mut self, curr: &[C], prev: &[C]) {
+        self.assign(curr, prev, PartialEq::eq, |color| (color, 0));
+    }
+    /// Diffs buffers of colors along with the index of the glyph drawn with them, for renderers that pick a glyph per cell
+    pub fn assign_glyphs(&mut self, curr: &[(C, u8)], prev: &[(C, u8)]) {
+        self.assign(curr, prev, PartialEq::eq, |cell| cell);
     }
     pub fn data(&self) -> &[(Range<usize>, C, u8)] {
         &self.data
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runs(current: &[u8], prev: &[u8]) -> Vec<(Range<usize>, u8)> {
+        BufferDiffIter::new(current, prev).collect()
+    }
+
+    #[test]
+    fn identical_buffers_have_no_runs() {
+        assert_eq!(runs(&[1, 2, 3, 4], &[1, 2, 3, 4]), []);
+        assert_eq!(runs(&[], &[]), []);
+    }
+
+    #[test]
+    fn run_at_the_end_of_the_buffer() {
+        assert_eq!(runs(&[1, 2, 7, 7], &[1, 2, 3, 4]), [(2..4, 7)]);
+        assert_eq!(runs(&[1, 2, 3, 9], &[1, 2, 3, 4]), [(3..4, 9)]);
+    }
+
+    #[test]
+    fn adjacent_runs_with_different_values() {
+        assert_eq!(
+            runs(&[5, 5, 6, 6, 6, 1], &[0, 0, 0, 0, 0, 1]),
+            [(0..2, 5), (2..5, 6)]
+        );
+        // a run also ends where the buffer matches the last one again, even if the value carries on
+        assert_eq!(runs(&[5, 5, 5, 5], &[0, 5, 0, 0]), [(0..1, 5), (2..4, 5)]);
+    }
+
+    #[test]
+    fn runs_within_a_tolerance() {
+        let close = |a: &u8, b: &u8| a.abs_diff(*b) <= 2;
+        let current = [10, 11, 12, 13, 20, 21, 0];
+        let prev = [0, 0, 0, 0, 0, 21, 0];
+        let runs: Vec<_> = BufferDiffIter::with_runs(&current, &prev, close).collect();
+        // 13 is too far from 10, where the run started, and the run stops at 21 since it didn't change
+        assert_eq!(runs, [(0..3, 10), (3..4, 13), (4..5, 20)]);
+    }
+
+    #[test]
+    fn dirty_rect_spans_the_changed_runs() {
+        let prev = [0; 12];
+        assert_eq!(dirty_rect(&prev, &prev, 4), None);
+        let mut current = prev;
+        current[5] = 1;
+        current[10] = 1;
+        assert_eq!(dirty_rect(&current, &prev, 4), Some((1, 1, 2, 2)));
+    }
+}
@@ -1,9 +1,106 @@
 use std::io::Write;
 pub trait Colorize: PartialEq + Default + Clone {
+    /// The distance between the levels each channel is quantized to, which ordered dithering spreads its offsets over.
+    /// Colors that aren't quantized don't need dithering
+    const STEP: i16 = 0;
     fn from_rgb(rgb: [u8; 3]) -> Self;
+    /// Like [`from_rgb`](Self::from_rgb), but the colors that can show any color are cut down to the nearest one in
+    /// `palette` when there is one
+    fn from_rgb_in(rgb: [u8; 3], _palette: Option<&Palette>) -> Self {
+        Self::from_rgb(rgb)
+    }
+    /// The color the terminal will actually show, which differs from the input when it was quantized
+    fn to_rgb(&self) -> [u8; 3];
     fn write_escape(&self, out: &mut impl Write) -> std::io::Result<()>;
 }
 
+/// The decimal digits of every byte and how many of them there are
+const DIGITS: [([u8; 3], usize); 256] = {
+    let mut digits = [([0; 3], 0); 256];
+    let mut i = 0;
+    while i < 256 {
+        let n = i as u8;
+        digits[i] = if n >= 100 {
+            ([b'0' + n / 100, b'0' + n / 10 % 10, b'0' + n % 10], 3)
+        } else if n >= 10 {
+            ([b'0' + n / 10, b'0' + n % 10, 0], 2)
+        } else {
+            ([b'0' + n, 0, 0], 1)
+        };
+        i += 1;
+    }
+    digits
+};
+
+/// An escape sequence built on the stack, which skips the formatting machinery of `write!`.
+/// That is around 5 times faster for a truecolor escape
+#[derive(Clone, Copy)]
+struct Escape {
+    // the longest escape is 36 bytes, and the rest is slack so a number can always copy all 3 digits
+    bytes: [u8; 40],
+    len: usize,
+}
+
+impl Escape {
+    const fn new() -> Self {
+        Self {
+            bytes: [0; 40],
+            len: 0,
+        }
+    }
+    /// A copy with `bytes` appended, for building the escape tables at compile time
+    const fn with(mut self, bytes: &[u8]) -> Self {
+        let mut i = 0;
+        while i < bytes.len() {
+            self.bytes[self.len] = bytes[i];
+            self.len += 1;
+            i += 1;
+        }
+        self
+    }
+    const fn with_number(self, n: u8) -> Self {
+        let (digits, len) = DIGITS[n as usize];
+        let mut this = self.with(&digits);
+        this.len -= 3 - len;
+        this
+    }
+    fn push(&mut self, bytes: &[u8]) {
+        self.bytes[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+    fn push_number(&mut self, n: u8) {
+        let (digits, len) = &DIGITS[n as usize];
+        self.bytes[self.len..self.len + 3].copy_from_slice(digits);
+        self.len += len;
+    }
+    /// `prefix` followed by each channel separated with `;`
+    fn push_rgb(&mut self, prefix: &[u8], [r, g, b]: [u8; 3]) {
+        self.push(prefix);
+        self.push_number(r);
+        self.push(b";");
+        self.push_number(g);
+        self.push(b";");
+        self.push_number(b);
+    }
+    fn write(&self, out: &mut impl Write) -> std::io::Result<()> {
+        out.write_all(&self.bytes[..self.len])
+    }
+}
+
+/// The escape for every color in the 256 color palette after `prefix`
+const fn ansi256_escapes(prefix: &[u8]) -> [Escape; 256] {
+    let mut escapes = [Escape::new(); 256];
+    let mut i = 0;
+    while i < 256 {
+        escapes[i] = Escape::new().with(prefix).with_number(i as u8).with(b"m");
+        i += 1;
+    }
+    escapes
+}
+
+static ANSI256_FOREGROUND: [Escape; 256] = ansi256_escapes(b"\x1b[38;5;");
+static ANSI256_BACKGROUND: [Escape; 256] = ansi256_escapes(b"\x1b[48;5;");
+
 #[derive(Clone, Copy, PartialEq, Default)]
 pub struct Rgb([u8; 3]);
 
@@ -11,9 +108,17 @@ impl Colorize for Rgb {
     fn from_rgb(rgb: [u8; 3]) -> Self {
         Self(rgb)
     }
+    fn from_rgb_in(rgb: [u8; 3], palette: Option<&Palette>) -> Self {
+        Self(palette.map_or(rgb, |palette| palette.quantize(rgb)))
+    }
+    fn to_rgb(&self) -> [u8; 3] {
+        self.0
+    }
     fn write_escape(&self, out: &mut impl Write) -> std::io::Result<()> {
-        let Self([r, g, b]) = *self;
-        write!(out, "\x1b[38;2;{r};{g};{b}m")
+        let mut escape = Escape::new();
+        escape.push_rgb(b"\x1b[38;2;", self.0);
+        escape.push(b"m");
+        escape.write(out)
     }
 }
 
@@ -24,9 +129,17 @@ impl Colorize for BackgroundRgb {
     fn from_rgb(rgb: [u8; 3]) -> Self {
         Self(rgb)
     }
+    fn from_rgb_in(rgb: [u8; 3], palette: Option<&Palette>) -> Self {
+        Self(palette.map_or(rgb, |palette| palette.quantize(rgb)))
+    }
+    fn to_rgb(&self) -> [u8; 3] {
+        self.0
+    }
     fn write_escape(&self, out: &mut impl Write) -> std::io::Result<()> {
-        let Self([r, g, b]) = *self;
-        write!(out, "\x1b[48;2;{r};{g};{b}m")
+        let mut escape = Escape::new();
+        escape.push_rgb(b"\x1b[48;2;", self.0);
+        escape.push(b"m");
+        escape.write(out)
     }
 }
 
@@ -34,12 +147,17 @@ impl Colorize for BackgroundRgb {
 pub struct Ansi256(u8);
 
 impl Colorize for Ansi256 {
+    // the spacing of the color cube
+    const STEP: i16 = 51;
     fn from_rgb(rgb: [u8; 3]) -> Self {
         Self(ansi_colours::ansi256_from_rgb(rgb))
     }
+    fn to_rgb(&self) -> [u8; 3] {
+        let (r, g, b) = ansi_colours::rgb_from_ansi256(self.0);
+        [r, g, b]
+    }
     fn write_escape(&self, out: &mut impl Write) -> std::io::Result<()> {
-        let ansi = self.0;
-        write!(out, "\x1b[38;5;{ansi}m")
+        ANSI256_FOREGROUND[self.0 as usize].write(out)
     }
 }
 
@@ -47,11 +165,473 @@ impl Colorize for Ansi256 {
 pub struct BackgroundAnsi256(u8);
 
 impl Colorize for BackgroundAnsi256 {
+    const STEP: i16 = 51;
     fn from_rgb(rgb: [u8; 3]) -> Self {
         Self(ansi_colours::ansi256_from_rgb(rgb))
     }
+    fn to_rgb(&self) -> [u8; 3] {
+        let (r, g, b) = ansi_colours::rgb_from_ansi256(self.0);
+        [r, g, b]
+    }
+    fn write_escape(&self, out: &mut impl Write) -> std::io::Result<()> {
+        ANSI256_BACKGROUND[self.0 as usize].write(out)
+    }
+}
+
+/// The 16 standard colors as xterm draws them by default. Other terminals use their own themes,
+/// but they're usually close enough
+const ANSI16_PALETTE: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [205, 0, 0],
+    [0, 205, 0],
+    [205, 205, 0],
+    [0, 0, 238],
+    [205, 0, 205],
+    [0, 205, 205],
+    [229, 229, 229],
+    [127, 127, 127],
+    [255, 0, 0],
+    [0, 255, 0],
+    [255, 255, 0],
+    [92, 92, 255],
+    [255, 0, 255],
+    [0, 255, 255],
+    [255, 255, 255],
+];
+
+/// The index of the color in `palette` with the smallest distance to `rgb`
+fn nearest(palette: &[[u8; 3]], rgb: [u8; 3]) -> usize {
+    let distance = |color: &[u8; 3]| -> u32 {
+        color
+            .iter()
+            .zip(rgb)
+            .map(|(&a, b)| (a as i32 - b as i32).pow(2) as u32)
+            .sum()
+    };
+    (0..palette.len())
+        .min_by_key(|&i| distance(&palette[i]))
+        .unwrap()
+}
+
+fn nearest_ansi16(rgb: [u8; 3]) -> u8 {
+    nearest(&ANSI16_PALETTE, rgb) as u8
+}
+
+/// The escapes for the colors in the 16 color palette, where `base` is 30 for the foreground and 40 for the background
+const fn ansi16_escapes(base: u8) -> [Escape; 16] {
+    let mut escapes = [Escape::new(); 16];
+    let mut index = 0;
+    while index < 16 {
+        // the bright colors are a separate range of codes
+        let code = if index < 8 {
+            base + index
+        } else {
+            base + 60 + index - 8
+        };
+        escapes[index as usize] = Escape::new().with(b"\x1b[").with_number(code).with(b"m");
+        index += 1;
+    }
+    escapes
+}
+
+static ANSI16_FOREGROUND: [Escape; 16] = ansi16_escapes(30);
+static ANSI16_BACKGROUND: [Escape; 16] = ansi16_escapes(40);
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub struct Ansi16(u8);
+
+impl Colorize for Ansi16 {
+    const STEP: i16 = 128;
+    fn from_rgb(rgb: [u8; 3]) -> Self {
+        Self(nearest_ansi16(rgb))
+    }
+    fn to_rgb(&self) -> [u8; 3] {
+        ANSI16_PALETTE[self.0 as usize]
+    }
+    fn write_escape(&self, out: &mut impl Write) -> std::io::Result<()> {
+        ANSI16_FOREGROUND[self.0 as usize].write(out)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub struct BackgroundAnsi16(u8);
+
+impl Colorize for BackgroundAnsi16 {
+    const STEP: i16 = 128;
+    fn from_rgb(rgb: [u8; 3]) -> Self {
+        Self(nearest_ansi16(rgb))
+    }
+    fn to_rgb(&self) -> [u8; 3] {
+        ANSI16_PALETTE[self.0 as usize]
+    }
+    fn write_escape(&self, out: &mut impl Write) -> std::io::Result<()> {
+        ANSI16_BACKGROUND[self.0 as usize].write(out)
+    }
+}
+
+/// Parses a color given as RRGGBB, with or without a leading #
+pub fn parse_hex(s: &str) -> Result<[u8; 3], String> {
+    let invalid = || format!("`{s}` is not a valid color, expected RRGGBB like 1e1e2e");
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+    Ok([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// How many bits of each channel index the lookup table of a [`Palette`]
+const LOOKUP_BITS: u32 = 5;
+
+/// A fixed set of up to 256 colors that [`Rgb`] and [`BackgroundRgb`] can be quantized to, like the palette of an old
+/// console or a theme
+pub struct Palette {
+    colors: Vec<[u8; 3]>,
+    // the nearest color for every color with its channels cut to `LOOKUP_BITS`, since searching the whole palette
+    // for every cell is too slow with a big one
+    lookup: Box<[u8]>,
+}
+
+// the lookup table is too big to be worth printing
+impl std::fmt::Debug for Palette {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Palette")
+            .field("colors", &self.colors)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Palette {
+    /// `None` if there are no colors or more than 256 of them
+    pub fn new(colors: Vec<[u8; 3]>) -> Option<Self> {
+        if colors.is_empty() || colors.len() > 256 {
+            return None;
+        }
+        let levels = 1 << LOOKUP_BITS;
+        let shift = 8 - LOOKUP_BITS;
+        // the middle of the range of colors each entry stands for
+        let level = |i: usize| ((i << shift) + (1 << (shift - 1))) as u8;
+        let lookup = (0..levels * levels * levels)
+            .map(|i| {
+                let rgb = [
+                    level(i / levels / levels),
+                    level(i / levels % levels),
+                    level(i % levels),
+                ];
+                nearest(&colors, rgb) as u8
+            })
+            .collect();
+        Some(Self { colors, lookup })
+    }
+    /// Parses a palette file, which has a color per line as RRGGBB with or without a leading #, like the .hex
+    /// palettes from Lospec. Blank lines and lines starting with `;` or `//` are skipped
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let colors = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| {
+                !line.is_empty() && !line.starts_with(';') && !line.starts_with("//")
+            })
+            .map(|(number, line)| parse_hex(line).map_err(|err| format!("line {number}: {err}")))
+            .collect::<Result<Vec<_>, _>>()?;
+        match colors.len() {
+            0 => Err("there are no colors in the palette".into()),
+            len @ 257.. => Err(format!(
+                "the palette has {len} colors, but at most 256 are supported"
+            )),
+            _ => Ok(Self::new(colors).unwrap()),
+        }
+    }
+    pub fn colors(&self) -> &[[u8; 3]] {
+        &self.colors
+    }
+    /// The color closest to `rgb`
+    pub fn quantize(&self, rgb: [u8; 3]) -> [u8; 3] {
+        self.colors[self.nearest(rgb) as usize]
+    }
+    /// The index of the color closest to `rgb`
+    fn nearest(&self, [r, g, b]: [u8; 3]) -> u8 {
+        let shift = 8 - LOOKUP_BITS;
+        let (r, g, b) = (
+            (r >> shift) as usize,
+            (g >> shift) as usize,
+            (b >> shift) as usize,
+        );
+        self.lookup[(r << (2 * LOOKUP_BITS)) | (g << LOOKUP_BITS) | b]
+    }
+}
+
+/// No color at all, for output that only has the characters
+#[derive(Clone, Copy, PartialEq, Default)]
+pub struct NoColor;
+
+impl Colorize for NoColor {
+    fn from_rgb(_: [u8; 3]) -> Self {
+        Self
+    }
+    /// The characters are drawn in whatever color the terminal uses for text
+    fn to_rgb(&self) -> [u8; 3] {
+        [255; 3]
+    }
+    fn write_escape(&self, _: &mut impl Write) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reduces a color to its luminance before handing it to `C`, so the same gray is emitted for every channel
+#[derive(Clone, Copy, PartialEq, Default)]
+pub struct Grayscale<C = Rgb>(C);
+
+pub type BackgroundGrayscale = Grayscale<BackgroundRgb>;
+
+impl<C: Colorize> Colorize for Grayscale<C> {
+    const STEP: i16 = C::STEP;
+    fn from_rgb(rgb: [u8; 3]) -> Self {
+        let lum = luminance(rgb);
+        Self(C::from_rgb([lum, lum, lum]))
+    }
+    fn from_rgb_in(rgb: [u8; 3], palette: Option<&Palette>) -> Self {
+        let lum = luminance(rgb);
+        Self(C::from_rgb_in([lum, lum, lum], palette))
+    }
+    fn to_rgb(&self) -> [u8; 3] {
+        self.0.to_rgb()
+    }
     fn write_escape(&self, out: &mut impl Write) -> std::io::Result<()> {
-        let ansi = self.0;
-        write!(out, "\x1b[48;5;{ansi}m")
+        self.0.write_escape(out)
+    }
+}
+
+/// A truecolor foreground and background together, for cells that show two colors like half and quadrant blocks
+#[derive(Clone, Copy, PartialEq, Default)]
+pub struct HalfBlock {
+    top: [u8; 3],
+    bottom: [u8; 3],
+}
+
+impl HalfBlock {
+    pub fn new(top: [u8; 3], bottom: [u8; 3]) -> Self {
+        Self { top, bottom }
+    }
+}
+
+impl Colorize for HalfBlock {
+    /// Both halves of the cell get the same color
+    fn from_rgb(rgb: [u8; 3]) -> Self {
+        Self::new(rgb, rgb)
+    }
+    /// The average of the two halves
+    fn to_rgb(&self) -> [u8; 3] {
+        let mut rgb = [0; 3];
+        for (channel, (top, bottom)) in rgb.iter_mut().zip(self.top.iter().zip(self.bottom)) {
+            *channel = ((*top as u16 + bottom as u16) / 2) as u8;
+        }
+        rgb
+    }
+    fn write_escape(&self, out: &mut impl Write) -> std::io::Result<()> {
+        let mut escape = Escape::new();
+        escape.push_rgb(b"\x1b[38;2;", self.top);
+        escape.push_rgb(b";48;2;", self.bottom);
+        escape.push(b"m");
+        escape.write(out)
+    }
+}
+
+pub const fn luminance(rgb: [u8; 3]) -> u8 {
+    let [r, g, b] = rgb;
+    (((r as u32) * 3 + (b as u32) + ((g as u32) << 2)) >> 3) as u8
+}
+
+/// How much each channel counts towards the luminance that picks the characters in ascii mode, in 256ths so it stays
+/// integer math
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LumaWeights([u16; 3]);
+
+impl LumaWeights {
+    /// The same weights as [`luminance`]
+    pub const DEFAULT: Self = Self([96, 128, 32]);
+
+    /// Scales the weights of the red, green, and blue channels to add up to 1, or returns `None` if that isn't possible
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn new([r, g, b]: [f32; 3]) -> Option<Self> {
+        let total = r + g + b;
+        if [r, g, b]
+            .iter()
+            .any(|weight| !weight.is_finite() || *weight < 0.)
+            || total <= 0.
+        {
+            return None;
+        }
+        let r = (r / total * 256.) as u16;
+        let g = (g / total * 256.) as u16;
+        // rounding everything down would leave some weight over, so blue gets the rest, which also keeps a white pixel
+        // from going past 255
+        Some(Self([r, g, 256 - r - g]))
+    }
+    pub fn apply(&self, [r, g, b]: [u8; 3]) -> u8 {
+        let [wr, wg, wb] = self.0;
+        ((r as u32 * wr as u32 + g as u32 * wg as u32 + b as u32 * wb as u32) >> 8) as u8
+    }
+}
+
+impl Default for LumaWeights {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// How many pixels [`luminance_chunk`] works on at once
+pub const LUMINANCE_CHUNK: usize = 16;
+
+/// The luminance of a chunk of RGBx pixels with `weights`, which is done 16 at a time with SSE2 with the `simd` feature
+pub fn luminance_chunk(
+    pixels: &[[u8; 4]; LUMINANCE_CHUNK],
+    weights: LumaWeights,
+) -> [u8; LUMINANCE_CHUNK] {
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    {
+        // SAFETY: sse2 is part of the x86_64 baseline
+        unsafe { sse2::luminance_chunk(pixels, weights) }
+    }
+    #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+    pixels.map(|[r, g, b, _]| weights.apply([r, g, b]))
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod sse2 {
+    use std::arch::x86_64::*;
+
+    use super::{LumaWeights, LUMINANCE_CHUNK};
+
+    /// The luminance of 4 pixels as 32 bit lanes
+    #[inline(always)]
+    unsafe fn luminance4(pixels: __m128i, weights: __m128i) -> __m128i {
+        let zero = _mm_setzero_si128();
+        // widen to 16 bits so the weighted channels don't overflow
+        let (low, high) = (
+            _mm_unpacklo_epi8(pixels, zero),
+            _mm_unpackhi_epi8(pixels, zero),
+        );
+        // each pixel becomes two lanes, r * wr + g * wg and b * wb
+        let (low, high) = (_mm_madd_epi16(low, weights), _mm_madd_epi16(high, weights));
+        // add the pairs, leaving the sums in the even lanes
+        let low = _mm_add_epi32(low, _mm_srli_epi64(low, 32));
+        let high = _mm_add_epi32(high, _mm_srli_epi64(high, 32));
+        let low = _mm_shuffle_epi32(low, 0b00_00_10_00);
+        let high = _mm_shuffle_epi32(high, 0b00_00_10_00);
+        _mm_srli_epi32(_mm_unpacklo_epi64(low, high), 8)
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn luminance_chunk(
+        pixels: &[[u8; 4]; LUMINANCE_CHUNK],
+        weights: LumaWeights,
+    ) -> [u8; LUMINANCE_CHUNK] {
+        // the weights are at most 256, so they fit in the signed lanes
+        let [r, g, b] = weights.0.map(|weight| weight as i16);
+        let weights = _mm_set_epi16(0, b, g, r, 0, b, g, r);
+        let ptr = pixels.as_ptr().cast::<__m128i>();
+        let [a, b, c, d] = [0, 1, 2, 3].map(|i| luminance4(_mm_loadu_si128(ptr.add(i)), weights));
+        // every sum fits in a byte, so the saturating packs don't change anything
+        let packed = _mm_packus_epi16(_mm_packs_epi32(a, b), _mm_packs_epi32(c, d));
+        let mut out = [0; LUMINANCE_CHUNK];
+        _mm_storeu_si128(out.as_mut_ptr().cast(), packed);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palette_quantizes_to_the_nearest_color() {
+        let palette =
+            Palette::parse("; a comment\n#000000\n\nff0000\n// another\n0000ff\n").unwrap();
+        assert_eq!(palette.colors(), [[0, 0, 0], [255, 0, 0], [0, 0, 255]]);
+        assert_eq!(palette.quantize([200, 30, 20]), [255, 0, 0]);
+        assert_eq!(palette.quantize([10, 20, 230]), [0, 0, 255]);
+        assert_eq!(palette.quantize([20, 20, 20]), [0, 0, 0]);
+    }
+
+    #[test]
+    fn palette_is_skipped_without_one() {
+        assert_eq!(Rgb::from_rgb_in([1, 2, 3], None).to_rgb(), [1, 2, 3]);
+        assert_eq!(
+            BackgroundRgb::from_rgb_in([1, 2, 3], None).to_rgb(),
+            [1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn grayscale_picks_the_palette_color_nearest_the_luminance() {
+        // the luminance of pure green is the brightest of the primaries, which is nearest the light color
+        let palette = Palette::new(vec![[20, 20, 60], [230, 200, 160]]).unwrap();
+        assert_eq!(
+            Rgb::from_rgb_in([0, 255, 0], Some(&palette)).to_rgb(),
+            [20, 20, 60]
+        );
+        let gray = Grayscale::<Rgb>::from_rgb_in([0, 255, 0], Some(&palette));
+        assert_eq!(gray.to_rgb(), [230, 200, 160]);
+    }
+
+    #[test]
+    fn palette_rejects_empty_and_oversized_files() {
+        assert!(Palette::parse("; nothing\n").is_err());
+        assert!(Palette::parse(&"000000\n".repeat(257)).is_err());
+        assert_eq!(
+            Palette::parse("000000\nnope\n").err().unwrap(),
+            "line 2: `nope` is not a valid color, expected RRGGBB like 1e1e2e"
+        );
+    }
+
+    /// A xorshift generator, so the fuzzing is the same on every run
+    fn xorshift(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    #[test]
+    fn luminance_chunk_matches_every_pixel_on_its_own() {
+        let mut state = 0x9e37_79b9_7f4a_7c15;
+        let mut weights = vec![
+            LumaWeights::DEFAULT,
+            LumaWeights::new([1., 0., 0.]).unwrap(),
+            LumaWeights::new([0., 1., 0.]).unwrap(),
+            LumaWeights::new([0., 0., 1.]).unwrap(),
+            LumaWeights::new([0.2126, 0.7152, 0.0722]).unwrap(),
+        ];
+        weights.extend((0..20).map(|_| {
+            let [r, g, b] = [0; 3].map(|_| (xorshift(&mut state) % 1000) as f32 + 1.);
+            LumaWeights::new([r, g, b]).unwrap()
+        }));
+        for weights in weights {
+            // the extremes, then noise
+            let mut chunks = vec![[[0; 4]; LUMINANCE_CHUNK], [[255; 4]; LUMINANCE_CHUNK]];
+            chunks.extend((0..200).map(|_| {
+                [0; LUMINANCE_CHUNK]
+                    .map(|_| xorshift(&mut state).to_le_bytes()[..4].try_into().unwrap())
+            }));
+            for chunk in chunks {
+                let expected = chunk.map(|[r, g, b, _]| weights.apply([r, g, b]));
+                assert_eq!(
+                    luminance_chunk(&chunk, weights),
+                    expected,
+                    "{weights:?} {chunk:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn luma_weights_add_up_to_one() {
+        assert_eq!(LumaWeights::new([3., 4., 1.]), Some(LumaWeights::DEFAULT));
+        assert_eq!(LumaWeights::new([1., 1., 1.]).unwrap().apply([255; 3]), 255);
+        assert_eq!(LumaWeights::new([0., 0., 0.]), None);
+        assert_eq!(LumaWeights::new([1., -1., 1.]), None);
+        assert_eq!(LumaWeights::new([1., f32::NAN, 1.]), None);
     }
 }
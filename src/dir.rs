@@ -0,0 +1,324 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{sync_channel, Receiver, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::producer::{Error, FrameCounter};
+use crate::{PlayConfig, Playback, Producer, ProducerMessage};
+
+/// The extensions of the frames a [`DirProducer`] plays
+const FRAME_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg"];
+
+/// A [`Producer`] that plays a directory of numbered PNG or JPEG frames, like the ones
+/// `gst-launch-1.0 ... ! pngenc ! multifilesink` or `ffmpeg -i video frame-%04d.jpg` leave behind, without going
+/// through gstreamer.
+///
+/// The frames are sent one every `interval` from another thread, scaled to the size from [`resize`](Self::resize)
+/// the same way `GstProducer` scales them to its caps: an `Initialize` comes before the first frame at a new size,
+/// and frames the renderer didn't get to in time are dropped. They're stretched to the size, so the frames should
+/// already have the shape of the terminal
+pub struct DirProducer {
+    paths: Arc<[PathBuf]>,
+    interval: Duration,
+    size: Arc<Mutex<(u32, u32)>>,
+    frame_data: Arc<[Mutex<Vec<u8>>]>,
+    counter: Arc<FrameCounter>,
+    finished: Arc<AtomicBool>,
+}
+
+/// The number in a frame's file name, like 12 for `frame-0012.png`, so they play in order without zero padding
+fn frame_number(path: &Path) -> Option<u64> {
+    let stem = path.file_stem()?.to_str()?;
+    let digits = stem.rsplit(|c: char| !c.is_ascii_digit()).next()?;
+    digits.parse().ok()
+}
+
+impl DirProducer {
+    /// Finds the frames in `dir`, which are played in the order of the last number in their names.
+    /// Each one is scaled to `(width, height)` pixels, and `buffer` is how many frames can wait for the renderer
+    pub fn new(
+        dir: &Path,
+        interval: Duration,
+        (width, height): (u32, u32),
+        buffer: usize,
+    ) -> Result<Self, Error> {
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+            .map_err(|err| format!("failed to read {}: {err}", dir.display()))?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| {
+                path.extension().is_some_and(|extension| {
+                    FRAME_EXTENSIONS
+                        .iter()
+                        .any(|frame| extension.eq_ignore_ascii_case(frame))
+                })
+            })
+            .collect();
+        if paths.is_empty() {
+            return Err(format!("there are no PNG or JPEG frames in {}", dir.display()).into());
+        }
+        // files without a number go after the ones with, in the order of their names
+        paths.sort_by(|a, b| {
+            let key = |path: &Path| (frame_number(path).unwrap_or(u64::MAX), path.to_owned());
+            key(a).cmp(&key(b))
+        });
+        Ok(Self {
+            paths: paths.into(),
+            interval,
+            size: Arc::new(Mutex::new((width, height))),
+            frame_data: (0..buffer + 2).map(|_| Mutex::new(Vec::new())).collect(),
+            counter: Arc::new(FrameCounter {
+                dropped: AtomicUsize::new(0),
+                not_dropped: AtomicUsize::new(0),
+                throttled: AtomicUsize::new(0),
+                late: AtomicUsize::new(0),
+                bad: AtomicUsize::new(0),
+                merged: AtomicUsize::new(0),
+            }),
+            finished: Arc::new(AtomicBool::new(false)),
+        })
+    }
+    /// Changes the size the frames are scaled to, like after the terminal is resized.
+    /// A new `Initialize` is sent with the next frame
+    pub fn resize(&self, width: u32, height: u32) {
+        *self.size.lock().unwrap() = (width, height);
+    }
+    pub fn counter(&self) -> Arc<FrameCounter> {
+        self.counter.clone()
+    }
+}
+
+/// Reads the image at `path` as RGBx at its own size
+fn decode(path: &Path) -> Result<(u32, u32, Vec<u8>), Error> {
+    let image = image::open(path)?.to_rgb8();
+    let (width, height) = image.dimensions();
+    let rgbx = image
+        .into_raw()
+        .chunks_exact(3)
+        .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 0])
+        .collect();
+    Ok((width, height, rgbx))
+}
+
+/// Scales an RGBx image to `(width, height)` into `out` with the nearest pixel, which is good enough for frames that
+/// end up as terminal cells anyway
+fn scale(
+    src: &[u8],
+    (src_width, src_height): (u32, u32),
+    (width, height): (u32, u32),
+    out: &mut Vec<u8>,
+) {
+    out.clear();
+    for y in 0..height as usize {
+        let src_y = y * src_height as usize / height as usize;
+        for x in 0..width as usize {
+            let src_x = x * src_width as usize / width as usize;
+            let i = (src_y * src_width as usize + src_x) * 4;
+            out.extend_from_slice(&src[i..i + 4]);
+        }
+    }
+}
+
+/// Sends every frame in `paths` to `notify`, stopping early if the receiver went away
+fn play(
+    paths: &[PathBuf],
+    interval: Duration,
+    size: &Mutex<(u32, u32)>,
+    frame_data: &[Mutex<Vec<u8>>],
+    counter: &FrameCounter,
+    notify: &SyncSender<ProducerMessage>,
+) -> Result<(), Error> {
+    let start = Instant::now();
+    let mut dims = None;
+    let mut slot = 0;
+    for (i, path) in paths.iter().enumerate() {
+        let pts = interval * i as u32;
+        if let Some(wait) = pts.checked_sub(start.elapsed()) {
+            thread::sleep(wait);
+        }
+        let (src_width, src_height, image) =
+            decode(path).map_err(|err| format!("failed to decode {}: {err}", path.display()))?;
+        let (width, height) = *size.lock().unwrap();
+        scale(
+            &image,
+            (src_width, src_height),
+            (width, height),
+            &mut frame_data[slot].lock().unwrap(),
+        );
+        if dims != Some((width, height)) {
+            notify.send(ProducerMessage::Initialize { width, height })?;
+            dims = Some((width, height));
+        }
+        // when the channel is full, the slot is overwritten by the next frame
+        match notify.try_send(ProducerMessage::FrameReady {
+            slot,
+            pts: Some(pts),
+        }) {
+            Err(TrySendError::Full(_)) => {
+                counter.dropped.fetch_add(1, Ordering::SeqCst);
+                continue;
+            }
+            Err(err) => return Err(err.into()),
+            Ok(()) => slot = (slot + 1) % frame_data.len(),
+        }
+        counter.not_dropped.fetch_add(1, Ordering::SeqCst);
+    }
+    Ok(())
+}
+
+impl Producer for DirProducer {
+    /// Starts sending the frames from the first one on another thread
+    fn subscribe(&mut self) -> Receiver<ProducerMessage> {
+        let (notify, recv) = sync_channel(self.frame_data.len() - 2);
+        let (paths, size, frame_data) = (
+            self.paths.clone(),
+            self.size.clone(),
+            self.frame_data.clone(),
+        );
+        let (counter, finished, interval) =
+            (self.counter.clone(), self.finished.clone(), self.interval);
+        finished.store(false, Ordering::SeqCst);
+        thread::spawn(move || {
+            let result = play(&paths, interval, &size, &frame_data, &counter, &notify);
+            finished.store(true, Ordering::SeqCst);
+            let _ = notify.send(match result {
+                Ok(()) => ProducerMessage::Eos,
+                Err(err) => ProducerMessage::Error {
+                    message: err.to_string(),
+                },
+            });
+        });
+        recv
+    }
+    fn swap_frame(&self, slot: usize, frame: &mut Vec<u8>) -> bool {
+        match self.frame_data.get(slot) {
+            Some(data) => {
+                std::mem::swap(&mut *data.lock().unwrap(), frame);
+                true
+            }
+            None => false,
+        }
+    }
+    fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::SeqCst)
+    }
+}
+
+impl Playback for DirProducer {
+    fn counter(&self) -> Arc<FrameCounter> {
+        self.counter.clone()
+    }
+    fn resize(&self, config: &PlayConfig, size: (u16, u16)) -> Result<(), Error> {
+        if let Some((width, height)) = config.video_size(size) {
+            DirProducer::resize(self, width, height);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    /// Plays every frame in `dir` at `size` as fast as it can, with room in the channel for all of them
+    fn frames(dir: &str, size: (u32, u32)) -> (Vec<(u32, u32)>, Vec<Vec<u8>>) {
+        let mut producer = DirProducer::new(&fixture(dir), Duration::ZERO, size, 8).unwrap();
+        let recv = producer.subscribe();
+        let (mut sizes, mut frames) = (Vec::new(), Vec::new());
+        loop {
+            match recv.recv().unwrap() {
+                ProducerMessage::Initialize { width, height } => sizes.push((width, height)),
+                ProducerMessage::FrameReady { slot, .. } => {
+                    let mut frame = Vec::new();
+                    assert!(producer.swap_frame(slot, &mut frame));
+                    frames.push(frame);
+                }
+                ProducerMessage::Error { message } => panic!("{message}"),
+                ProducerMessage::Eos => break,
+            }
+        }
+        assert!(producer.is_finished());
+        assert_eq!(producer.counter().dropped.load(Ordering::SeqCst), 0);
+        (sizes, frames)
+    }
+
+    #[test]
+    fn frame_numbers() {
+        assert_eq!(frame_number(Path::new("frame-0012.png")), Some(12));
+        assert_eq!(frame_number(Path::new("take2-frame7.jpg")), Some(7));
+        assert_eq!(frame_number(Path::new("frame.png")), None);
+    }
+
+    #[test]
+    fn plays_in_numeric_order() {
+        let (sizes, frames) = frames("frames", (2, 2));
+        // notes.txt isn't a frame, and frame-10 comes after frame-2
+        assert_eq!(sizes, [(2, 2)]);
+        assert_eq!(
+            frames,
+            [
+                [
+                    [255, 0, 0, 0],
+                    [0, 255, 0, 0],
+                    [0, 0, 255, 0],
+                    [255, 255, 255, 0]
+                ]
+                .concat(),
+                // frame-2 is 4x4 and each of its quadrants is one pixel at 2x2
+                [
+                    [10, 20, 30, 0],
+                    [40, 50, 60, 0],
+                    [70, 80, 90, 0],
+                    [100, 110, 120, 0]
+                ]
+                .concat(),
+                [0; 16].to_vec(),
+            ]
+        );
+    }
+
+    #[test]
+    fn scales_to_the_size() {
+        let (sizes, frames) = frames("frames", (4, 1));
+        assert_eq!(sizes, [(4, 1)]);
+        assert_eq!(
+            frames[0],
+            [
+                [255, 0, 0, 0],
+                [255, 0, 0, 0],
+                [0, 255, 0, 0],
+                [0, 255, 0, 0]
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn reads_jpeg() {
+        // an 8x8 grayscale JPEG that's 144 everywhere
+        let (sizes, frames) = frames("jpeg", (2, 1));
+        assert_eq!(sizes, [(2, 1)]);
+        assert_eq!(frames, [[144, 144, 144, 0, 144, 144, 144, 0]]);
+    }
+
+    #[test]
+    fn empty_dir() {
+        let err = DirProducer::new(&fixture("."), Duration::ZERO, (1, 1), 1)
+            .err()
+            .unwrap();
+        assert!(err
+            .to_string()
+            .starts_with("there are no PNG or JPEG frames in"));
+    }
+}
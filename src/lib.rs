@@ -0,0 +1,25 @@
+//! Real-time terminal video player.
+//!
+//! [`play`] draws a video into any writer, and the modules have the pieces it's built from for finer control:
+//! a [`Producer`] decodes frames, and a [`Renderer`] turns them into escape sequences with a [`Colorize`] color.
+pub mod buffer;
+pub mod color;
+pub mod dir;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod overlay;
+mod player;
+pub mod producer;
+pub mod record;
+pub mod render;
+pub mod resize_watcher;
+pub mod subtitle;
+pub mod term;
+
+pub use color::Colorize;
+pub use player::{
+    detect_colors, play, Banner, BannerPosition, Colors, Mode, PlayConfig, Playback, Player,
+    Profile, Scale, ScaleMethod,
+};
+pub use producer::{Error, Producer, ProducerMessage};
+pub use render::{DynRenderer, Renderer};
@@ -1,192 +1,978 @@
-use clap::Parser;
-use gstreamer as gst;
-use producer::{FrameCounter, GstProducer, Producer, ProducerMessage};
-use render::{DefaultRenderer, KittyRenderer, Renderer};
+use clap::{Parser, ValueEnum};
+use onna::color::{self, LumaWeights, Palette};
+use onna::dir::DirProducer;
+use onna::producer::{Decoder, FrameCounter, ProducerOptions, Repeat, Source, StartAt};
+use onna::record::{self, CastWriter, Recorder};
+use onna::render::{Dither, Edges, RenderOptions, KITTY_CHUNK};
+use onna::subtitle::{self, Subtitles};
+use onna::term::{self, AltScreen, HideCursor, MouseReporting};
+use onna::{
+    detect_colors, Banner, BannerPosition, Colors, Mode, PlayConfig, Playback, Player, Scale,
+    ScaleMethod,
+};
 use std::error::Error;
-use std::io::Write;
-use std::ops::{Deref, DerefMut};
-use std::sync::atomic::AtomicBool;
-use std::sync::mpsc::Receiver;
+use std::io::{IsTerminal, Write};
+use std::sync::{atomic::Ordering, Arc};
 
 use std::{
-    io::{stdout, BufWriter},
-    path::PathBuf,
-    time::Duration,
+    fs::{File, OpenOptions},
+    io::{stdout, BufReader, BufWriter},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
-mod buffer;
-mod color;
-mod producer;
-mod render;
-
-use color::{Ansi256, BackgroundAnsi256, BackgroundRgb, Rgb};
 
 /// Play a video in the terminal from a file path or url.
 #[derive(Parser)]
 pub struct Args {
-    /// The file or url to play
-    video: String,
+    /// The files or urls to play, one after another, or - to read the video from stdin. Press n and p to skip to the next or previous one, and q to quit.
+    #[arg(required_unless_present_any = ["camera", "screen", "replay", "frames_dir"])]
+    video: Vec<String>,
+    /// Play from a camera instead of a file. Takes the device, which is a path like /dev/video0 on linux and an index elsewhere, and uses the first camera without one.
+    #[arg(long, num_args = 0..=1, conflicts_with = "video")]
+    camera: Option<Option<String>>,
+    /// Mirror the screen into the terminal. This needs ximagesrc from gst-plugins-good on linux, avfvideosrc from gst-plugins-good on macos, and d3d11screencapturesrc from gst-plugins-bad on windows.
+    #[arg(long, default_value_t = false, conflicts_with_all = ["video", "camera"])]
+    screen: bool,
+    /// Play a directory of numbered PNG or JPEG frames instead of a video, like the ones ffmpeg writes with `ffmpeg -i video frame-%04d.png`. They play in the order of the last number in their names at --fps, which is 24 by default, and are stretched to fill the terminal. This doesn't need gstreamer.
+    #[arg(long, conflicts_with_all = ["video", "camera", "screen", "replay", "url", "image", "kitty", "sixel", "iterm2", "yuv", "scale", "keep_aspect", "scale_method", "probe", "thumbnail", "start_at", "looping", "loop_count", "speed", "subtitles"])]
+    frames_dir: Option<PathBuf>,
+    /// Draw the video this many columns wide and rows tall as WxH instead of filling the terminal. This is also used when the size of the terminal can't be found, like when the output isn't a terminal.
+    #[arg(long, value_parser = parse_size)]
+    size: Option<(u16, u16)>,
+    /// Draw the video this many columns wide whatever the size of the terminal, keeping the height of the terminal unless --rows is passed too. The terminal isn't followed when it's resized. Anything past the edge of the terminal only shows up in --record, --cast, and --output.
+    #[arg(long, conflicts_with = "size", value_parser = clap::value_parser!(u16).range(1..))]
+    cols: Option<u16>,
+    /// Like --cols, but for the number of rows.
+    #[arg(long, conflicts_with = "size", value_parser = clap::value_parser!(u16).range(1..))]
+    rows: Option<u16>,
+    /// The width and height of a terminal cell as W:H or a single number for W/H, to fix a stretched video on fonts that aren't 1:2. It also applies to --half-block, but not to kitty, sixel, or iterm2, which draw in real pixels.
+    #[arg(long, default_value = "1:2", value_parser = parse_cell_aspect)]
+    cell_aspect: (u32, u32),
+    /// The size of a terminal cell in pixels as WxH, like 10x20, for kitty, sixel, and iterm2. By default it comes from the terminal, and the video is drawn at its own size when the terminal doesn't say. Set this when the terminal doesn't report its size in pixels, or reports it wrong.
+    #[arg(long, value_parser = parse_size)]
+    cell_px: Option<(u16, u16)>,
+    /// How to fit the video to the terminal when their shapes differ: stretch it to fill the terminal, scale it down to fit between bars, or scale it up to cover the terminal and crop the sides. Kitty, sixel, and iterm2 never stretch, and can only crop when the size of the cells in pixels is known.
+    #[arg(long, value_enum, default_value_t = Scale::Fill)]
+    scale: Scale,
+    /// How the video is scaled to the terminal, from the cheapest to the sharpest. Nearest keeps hard edges, which suits pixel art and ascii, and lanczos is the sharpest for photos. Every frame is scaled, so 4-tap and lanczos can take a lot more cpu on big videos. By default it's bilinear.
+    #[arg(long, value_enum)]
+    scale_method: Option<ScaleMethod>,
+    /// Scale the video to twice as many rows and average each pair of them into one cell, which keeps more of the detail than scaling straight to the terminal. Unlike --half-block, each cell is still a single color.
+    #[arg(long, default_value_t = false, conflicts_with_all = ["kitty", "sixel", "iterm2", "half_block", "quadrant", "sextant", "yuv"])]
+    squash: bool,
+    /// Keep the aspect ratio of the video instead of stretching it to fill the terminal, centering it between bars. This is the same as `--scale fit`.
+    #[arg(long, default_value_t = false, conflicts_with = "scale")]
+    keep_aspect: bool,
+    /// The color of the bars around the video with --scale fit as a hex color like 000000. By default, they're the terminal's background.
+    #[arg(long, value_parser = color::parse_hex)]
+    pad_color: Option<[u8; 3]>,
+    /// Save everything drawn to the terminal to this file, along with its timing, so it can be played back with --replay.
+    #[arg(long)]
+    record: Option<PathBuf>,
+    /// Save everything drawn to the terminal to this file as an asciinema cast, which can be played with `asciinema play` or uploaded to asciinema.org.
+    #[arg(long)]
+    cast: Option<PathBuf>,
+    /// Play back a file saved with --record. This doesn't need gstreamer, and skips the video entirely.
+    #[arg(long, conflicts_with_all = ["video", "camera", "screen", "record", "cast"])]
+    replay: Option<PathBuf>,
+    /// Show the video as a still image until you quit with q or ctrl+c. This is the default for files with an image extension.
+    #[arg(long, default_value_t = false)]
+    image: bool,
     /// Interpret the video as a file or url
     #[arg(short, long, default_value_t = false)]
     url: bool,
-    /// Use 256 colors instead of truecolor. This may speed up the rendering at the cost of color quality.
-    #[arg(short, long, default_value_t = false)]
+    /// How many colors to use. Fewer colors may speed up the rendering at the cost of color quality, and 16 colors work on nearly every terminal. By default, the best mode the terminal supports is picked from $COLORTERM and $TERM.
+    #[arg(long, value_enum, default_value_t = Colors::Auto)]
+    colors: Colors,
+    /// Don't warn when truecolor is used on a terminal that doesn't advertise it in $COLORTERM or $TERM.
+    #[arg(long, default_value_t = false)]
+    force_truecolor: bool,
+    /// Quantize to the colors in this file instead, which has a color per line as RRGGBB, like the .hex palettes from Lospec. Blank lines and lines starting with ; or // are skipped. The colors are written as truecolor.
+    #[arg(long, conflicts_with_all = ["kitty", "sixel", "iterm2", "half_block", "quadrant", "sextant", "no_color", "yuv", "colors", "ansi256"])]
+    palette: Option<PathBuf>,
+    /// Use 256 colors instead of truecolor. This is the same as `--colors 256`.
+    #[arg(short, long, default_value_t = false, conflicts_with = "colors")]
     ansi256: bool,
+    /// Dither the colors to hide the banding from the 16 or 256 color palette, with a Bayer matrix or the one in --dither=METHOD. Floyd-Steinberg (fs) looks the best, but it uses more cpu and makes more of the screen change between frames, which shrinks the savings from only redrawing what changed.
+    #[arg(long, value_name = "METHOD", value_enum, default_value_t = Dither::None, num_args = 0..=1, default_missing_value = "bayer", require_equals = true)]
+    dither: Dither,
+    /// Alternate the colors between the two nearest colors of the 16 or 256 color palette on every other frame, so gradients look smoother. This works with --dither, but makes more of the screen change between frames.
+    #[arg(long, default_value_t = false)]
+    temporal_dither: bool,
     /// The maximum amount of time to wait for the decoder to get the source capabilities
     #[arg(short, long, default_value_t = 5)]
     timeout: u64,
 
-    /// (Experimental and buggy) Use the kitty image protocol.
+    /// (Experimental) Use the kitty image protocol.
     #[arg(short, long, default_value_t = false)]
     kitty: bool,
+    /// Send the kitty images uncompressed. Compression cuts down the output a lot, but costs some cpu time per frame.
+    #[arg(long, default_value_t = false, requires = "kitty")]
+    kitty_no_compress: bool,
+    /// How many bytes of base64 to send in each kitty escape, which has to be a multiple of 4. The protocol only promises that 4096 works, but some terminals are faster with bigger chunks.
+    #[arg(long, default_value_t = KITTY_CHUNK, value_parser = parse_kitty_chunk, requires = "kitty")]
+    kitty_chunk: usize,
+    /// Use sixel graphics, which xterm, mlterm, foot, and other terminals support. Colors are limited to the 256 color palette.
+    #[arg(long, default_value_t = false, conflicts_with = "kitty")]
+    sixel: bool,
+    /// Use the iTerm2 inline image protocol, which iTerm2 and WezTerm support.
+    #[arg(long, default_value_t = false, conflicts_with_all = ["kitty", "sixel"])]
+    iterm2: bool,
+    /// Draw two pixels in each cell with half blocks, doubling the vertical resolution. This always uses truecolor.
+    #[arg(long, default_value_t = false, conflicts_with_all = ["kitty", "sixel", "iterm2"])]
+    half_block: bool,
+    /// Draw a 2x2 grid of pixels in each cell with quadrant blocks, doubling the resolution in both directions. Each cell only gets two of the four colors, and this always uses truecolor.
+    #[arg(long, default_value_t = false, conflicts_with_all = ["kitty", "sixel", "iterm2", "half_block"])]
+    quadrant: bool,
+    /// Draw a 2x3 grid of pixels in each cell with sextant blocks, for even more detail than --quadrant. These come from the Symbols for Legacy Computing block, which not every font has. If the video shows up as boxes or question marks, use --quadrant instead.
+    #[arg(long, default_value_t = false, conflicts_with_all = ["kitty", "sixel", "iterm2", "half_block", "quadrant"])]
+    sextant: bool,
+    /// Only draw the characters, without any color. This is useful for files and terminals without color support.
+    #[arg(long, default_value_t = false, conflicts_with_all = ["kitty", "sixel", "iterm2", "half_block", "quadrant", "sextant", "colors", "ansi256", "background", "grayscale"])]
+    no_color: bool,
+    /// Like --no-color, but have the decoder hand over its own I420 or NV12 frames and pick the characters straight from their brightness plane. The frames are never converted to RGB, which is the fastest way to play a video, especially with --no-convert.
+    #[arg(long, default_value_t = false, conflicts_with_all = ["kitty", "sixel", "iterm2", "half_block", "quadrant", "sextant", "no_color", "colors", "ansi256", "background", "grayscale", "luma_weights", "edges", "edges_mono", "dump_frame"])]
+    yuv: bool,
     /// Use the colors as the background of the pixel instead of the foreground. This is the recommended mode and may become default in the future.
     #[arg(short, long, default_value_t = false)]
     background: bool,
+    /// Render in shades of gray. This reduces the number of distinct colors, which shrinks the output.
+    #[arg(short, long, default_value_t = false)]
+    grayscale: bool,
+    /// Shift the brightness of every pixel, where 0 leaves it unchanged and 1 is the full range.
+    #[arg(long, default_value_t = 0., allow_negative_numbers = true)]
+    brightness: f32,
+    /// Scale the contrast of every pixel around the midpoint, where 1 leaves it unchanged.
+    #[arg(long, default_value_t = 1., allow_negative_numbers = true)]
+    contrast: f32,
+    /// The gamma applied to the colors. Defaults to 0.5 in ascii mode and 1.0 in the other modes.
+    #[arg(long)]
+    gamma: Option<f32>,
+    /// Invert the colors, which also inverts the characters picked in ascii mode.
+    #[arg(short, long, default_value_t = false)]
+    invert: bool,
+    /// How much the red, green, and blue channels count towards the brightness that picks the characters, like 3,4,1, which is the default. This doesn't change the colors. Raising the weight of a channel brings out more detail in videos that are mostly that color.
+    #[arg(long, value_parser = parse_luma_weights)]
+    luma_weights: Option<LumaWeights>,
+    /// Draw pixels darker than this brightness from 0 to 255 as blank spaces in ascii mode. Videos on black backgrounds look cleaner, and the flat dark areas are cheaper to draw.
+    #[arg(long, default_value_t = 0, conflicts_with_all = ["kitty", "sixel", "iterm2", "half_block", "quadrant", "sextant", "background"])]
+    black_threshold: u8,
+    /// Draw neighboring cells whose colors are at most this far apart in each channel from 0 to 255 in the same color, so noisy video takes fewer escapes to draw. This is lossy, and 0 keeps every color as it is.
+    #[arg(long, default_value_t = 0, conflicts_with_all = ["kitty", "sixel", "iterm2", "half_block", "quadrant", "sextant"])]
+    color_tolerance: u8,
+    /// Leave a cell alone until its color has moved by more than this in some channel from 0 to 255, so sensor noise and compression artifacts don't keep redrawing still scenes. 0 redraws every change.
+    #[arg(long, default_value_t = 0, conflicts_with_all = ["kitty", "sixel", "iterm2", "half_block", "quadrant", "sextant"])]
+    motion_threshold: u8,
+    /// Trace the outlines in the video with the characters instead of shading it, for a sketch-like look. This runs an edge filter over every frame, which costs more cpu, and the edges change a lot from frame to frame, so more of the screen has to be redrawn.
+    #[arg(long, default_value_t = false, conflicts_with_all = ["kitty", "sixel", "iterm2", "half_block", "quadrant", "sextant", "background"])]
+    edges: bool,
+    /// Like --edges, but draw every character in white instead of the color of the video.
+    #[arg(long, default_value_t = false, conflicts_with_all = ["kitty", "sixel", "iterm2", "half_block", "quadrant", "sextant", "background"])]
+    edges_mono: bool,
+    /// Use the REP escape to repeat runs of the same character. This shrinks the output on flat frames, but not every terminal supports it.
+    #[arg(long, default_value_t = false)]
+    use_rep: bool,
+    /// Draw each frame atomically with synchronized output to avoid tearing. By default, it is enabled when the terminal is known to support it.
+    #[arg(long, value_enum, default_value_t = SyncOutput::Auto)]
+    sync: SyncOutput,
+    /// Draw over the current screen instead of switching to the alternate screen, leaving the last frame behind on exit.
+    #[arg(long, default_value_t = false)]
+    no_altscreen: bool,
+    /// Clear the screen on exit instead of leaving the last frame behind with --no-altscreen.
+    #[arg(long, default_value_t = false, conflicts_with = "thumbnail")]
+    clear_on_exit: bool,
+    /// Show the framerate, the percentage of dropped frames, and the terminal size on the top row.
+    #[arg(long, default_value_t = false)]
+    stats: bool,
+    /// Show this text on a row of its own, like a label for a recording or a stream. It's cut off at the width of the terminal.
+    #[arg(long)]
+    banner: Option<String>,
+    /// Which edge of the terminal the banner goes on.
+    #[arg(long, value_enum, default_value_t = BannerPosition::Bottom, requires = "banner")]
+    banner_pos: BannerPosition,
+    /// Flush the output at most every 50ms instead of after every frame, or sooner when the buffer fills up. This saves a syscall per frame on fast terminals, at the cost of a little latency, and the output still goes out right away when playback waits or stops. It doesn't work with --record and --cast, which take the time of each frame from its flush.
+    #[arg(long, default_value_t = false, conflicts_with_all = ["record", "cast"])]
+    no_flush_per_frame: bool,
+    /// Don't convert the decoded video's pixel format. This saves a full conversion per frame when the decoder can already output RGBx (or RGBA in kitty mode), but the video will fail to play when it can't.
+    #[arg(long, default_value_t = false)]
+    no_convert: bool,
+    /// Which decoder to use. Hardware decoders take load off the cpu on machines that have them.
+    #[arg(long, value_enum, default_value_t = Decoder::Auto)]
+    decoder: Decoder,
+    /// Restart the video every time it ends.
+    #[arg(short, long = "loop", default_value_t = false)]
+    looping: bool,
+    /// Play the video this many times in total before exiting.
+    #[arg(long, conflicts_with = "looping", value_parser = clap::value_parser!(u32).range(1..))]
+    loop_count: Option<u32>,
+    /// Print the warnings from gstreamer to standard error.
+    #[arg(short, long, default_value_t = false)]
+    verbose: bool,
+    /// Begin playback at this time, either in seconds or as [HH:]MM:SS.
+    #[arg(long, value_parser = parse_timestamp)]
+    start_at: Option<Duration>,
+    /// Draw a single frame, 10% into the video or from the time in --thumbnail=TIME, and leave it on the screen. This is handy for previews of videos in the terminal.
+    #[arg(long, value_name = "TIME", value_parser = parse_timestamp, num_args = 0..=1, require_equals = true, conflicts_with_all = ["start_at", "replay", "record", "cast"])]
+    thumbnail: Option<Option<Duration>>,
+    /// The playback speed, where 1 is the normal speed and 0 starts paused. Audio is muted when it is far from 1.
+    #[arg(long, default_value_t = 1.)]
+    speed: f64,
+    /// How many decoded frames can wait to be rendered before new ones are dropped. A bigger buffer smooths over slow frames, but each frame in it puts the video a frame further behind the audio.
+    #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u32).range(1..))]
+    buffer: u32,
+    /// How many frames in a row can fail to be read before the video gives up. The broken ones are skipped until then, so a flaky stream keeps playing through the odd bad frame.
+    #[arg(long, default_value_t = 10)]
+    max_bad_samples: u32,
+    /// How many seconds to wait for the next frame before giving up, or 0 to wait forever. Defaults to 3 for files and forever for urls, stdin, cameras, and the screen, which can take a while to catch up.
+    #[arg(long, value_parser = parse_stall_timeout)]
+    stall_timeout: Option<f64>,
+    /// How many seconds of a url to download ahead of the video. More makes a slow connection stall less often, and playback pauses while it catches up.
+    #[arg(long)]
+    buffer_duration: Option<f64>,
+    /// How many milliseconds of an rtsp stream to buffer. Lower is closer to real time, but drops more frames on a bad network.
+    #[arg(long)]
+    rtsp_latency: Option<u32>,
+    /// Receive rtsp streams over tcp instead of udp, for networks that drop the udp packets.
+    #[arg(long, default_value_t = false)]
+    rtsp_tcp: bool,
+    /// Show the subtitles in the video on two rows under it, from the first track or the one in --subtitles=TRACK.
+    #[arg(long, value_name = "TRACK", num_args = 0..=1, default_missing_value = "0", require_equals = true, conflicts_with_all = ["camera", "screen"])]
+    subtitles: Option<i32>,
+    /// Show the subtitles from a SubRip (.srt) file on two rows under the video.
+    #[arg(long, conflicts_with = "subtitles")]
+    sub_file: Option<PathBuf>,
+    /// Render at most this many frames per second, skipping the rest. This saves cpu and bandwidth on high framerate videos.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    fps: Option<u32>,
+    /// Show each frame at the time its timestamp says instead of as soon as it's decoded, and skip the frames that come in too late. This is the default for gifs, so every frame gets its own delay.
+    #[arg(long, default_value_t = false)]
+    pace: bool,
+    /// When the terminal can't keep up, draw only the newest of the frames that piled up instead of each one in turn. The changes in the skipped frames still make it into the next one, so it looks smoother than dropping frames. With --fps, frames that come in too soon wait for their turn instead of being skipped.
+    #[arg(long, default_value_t = false, conflicts_with = "pace")]
+    batch: bool,
+    /// Decode and render the video as fast as it plays without drawing it, then print how many frames and bytes were rendered. This measures the rendering without a terminal getting in the way.
+    #[arg(long, default_value_t = false, conflicts_with_all = ["record", "cast", "thumbnail"])]
+    bench: bool,
+    /// Print the frame stats at exit as JSON instead of a sentence, for scripts to parse.
+    #[arg(long, default_value_t = false)]
+    stats_json: bool,
+    /// Also write the frame stats as JSON to this file at exit.
+    #[arg(long)]
+    stats_file: Option<PathBuf>,
+    /// Print the resolution, framerate, pixel format, duration, and tracks of the video, then exit without playing it.
+    #[arg(long, default_value_t = false, conflicts_with_all = ["record", "cast", "thumbnail", "bench"])]
+    probe: bool,
+    /// Write the video to this file, named pipe, or terminal device instead of stdout. The size still comes from this terminal unless --size is passed, and the alternate screen, hidden cursor, and mouse reporting are left out when it isn't a terminal.
+    #[arg(long, conflicts_with = "bench")]
+    output: Option<PathBuf>,
+    /// Save the Nth decoded frame, counting from 1 and including the ones skipped by --fps, --pace, and --batch, to a PNG at PATH exactly as the decoder scaled it, before any of the color adjustments. This shows whether odd colors come from gstreamer or from the rendering.
+    #[arg(long, num_args = 2, value_names = ["N", "PATH"])]
+    dump_frame: Option<Vec<String>>,
+    /// Time how long each frame takes to turn into cells, to render into escapes, and to write out to the terminal, and print the averages on standard error at the end. A slow write means the terminal is what's holding the video back.
+    #[arg(long, default_value_t = false)]
+    profile: bool,
+    /// Show a progress bar on the bottom row, or the elapsed time when the video's length is unknown. Click on the bar to seek there.
+    #[arg(long, default_value_t = false)]
+    progress: bool,
 }
 
-fn hide_cursor(mut out: impl Write) -> std::io::Result<()> {
-    out.write_all(b"\x1b[?25l")?;
-    Ok(())
+/// Parses a timestamp given either as bare (possibly fractional) seconds or as [HH:]MM:SS
+fn parse_timestamp(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("`{s}` is not a valid timestamp, expected seconds or [HH:]MM:SS");
+    let parts: Vec<_> = s.rsplit(':').collect();
+    if parts.len() > 3 {
+        return Err(invalid());
+    }
+    let seconds: f64 = parts[0]
+        .parse()
+        .ok()
+        .filter(|secs: &f64| secs.is_finite() && *secs >= 0.)
+        .ok_or_else(invalid)?;
+    let mut total = seconds;
+    for (part, scale) in parts[1..].iter().zip([60., 3600.]) {
+        let value: u32 = part.parse().map_err(|_| invalid())?;
+        total += value as f64 * scale;
+    }
+    Ok(Duration::from_secs_f64(total))
 }
-fn show_cursor(mut out: impl Write) -> std::io::Result<()> {
-    out.write_all(b"\x1b[?25h")?;
-    Ok(())
+
+/// Parses a number of seconds that is 0 or more, since a negative or NaN timeout would quietly mean waiting forever
+fn parse_stall_timeout(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(secs) if secs.is_finite() && secs >= 0. => Ok(secs),
+        _ => Err(format!(
+            "`{s}` is not a valid timeout, expected a number of seconds like 3, or 0 to wait forever"
+        )),
+    }
 }
 
-/// A wrapper around a `Write` that hides the cursor on creation and shows it again on drop
-pub struct HideCursor<W: Write>(W);
-impl<W: Write> HideCursor<W> {
-    pub fn new(mut writer: W) -> Self {
-        let _ = hide_cursor(&mut writer);
-        Self(writer)
+/// Parses a kitty chunk size in bytes, which has to be a multiple of 4 so each chunk decodes on its own
+fn parse_kitty_chunk(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(bytes) if bytes > 0 && bytes.is_multiple_of(4) => Ok(bytes),
+        _ => Err(format!(
+            "`{s}` is not a valid chunk size, expected a multiple of 4 like {KITTY_CHUNK}"
+        )),
     }
-    pub fn show(&mut self) -> std::io::Result<()> {
-        show_cursor(&mut self.0)
+}
+
+/// Parses a size given as WxH
+fn parse_size(s: &str) -> Result<(u16, u16), String> {
+    let invalid = || format!("`{s}` is not a valid size, expected WxH like 80x24");
+    let (width, height) = s.split_once(['x', 'X']).ok_or_else(invalid)?;
+    let width: u16 = width.parse().map_err(|_| invalid())?;
+    let height: u16 = height.parse().map_err(|_| invalid())?;
+    if width == 0 || height == 0 {
+        return Err(invalid());
     }
+    Ok((width, height))
+}
+
+/// Parses three weights for the red, green, and blue channels separated by commas
+fn parse_luma_weights(s: &str) -> Result<LumaWeights, String> {
+    let invalid = || {
+        format!("`{s}` is not a valid set of weights, expected three positive numbers like 3,4,1")
+    };
+    let weights: Vec<f32> = s
+        .split(',')
+        .map(|weight| weight.trim().parse().map_err(|_| invalid()))
+        .collect::<Result<_, _>>()?;
+    let weights = weights.try_into().map_err(|_| invalid())?;
+    LumaWeights::new(weights).ok_or_else(invalid)
 }
-impl<W: Write> Drop for HideCursor<W> {
-    fn drop(&mut self) {
-        let _ = self.show();
+
+/// Parses a cell aspect ratio given as W:H or as the width divided by the height
+fn parse_cell_aspect(s: &str) -> Result<(u32, u32), String> {
+    let invalid =
+        || format!("`{s}` is not a valid aspect ratio, expected W:H like 1:2 or a number like 0.5");
+    let (width, height) = match s.split_once(':') {
+        Some((width, height)) => (
+            width.parse().map_err(|_| invalid())?,
+            height.parse().map_err(|_| invalid())?,
+        ),
+        None => {
+            let ratio: f64 = s.parse().map_err(|_| invalid())?;
+            if !(ratio.is_finite() && ratio > 0.) {
+                return Err(invalid());
+            }
+            // caps only take fractions
+            ((ratio * 1000.).round() as u32, 1000)
+        }
+    };
+    if width == 0 || height == 0 {
+        return Err(invalid());
     }
+    Ok((width, height))
 }
-impl<W: Write> Deref for HideCursor<W> {
-    type Target = W;
-    fn deref(&self) -> &Self::Target {
-        &self.0
+
+/// How often the output is flushed with --no-flush-per-frame, which is a few frames of a typical video
+const BATCHED_FLUSH_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How many frames a second --frames-dir plays without --fps
+const FRAMES_DIR_FPS: u32 = 24;
+
+/// How long a file can go without a frame before it's given up on
+const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The size used when there's no --size and the terminal size can't be found
+const FALLBACK_SIZE: (u16, u16) = (80, 24);
+
+impl Args {
+    /// Whether the size of the video was picked on the command line, so it doesn't follow the terminal
+    fn fixed_size(&self) -> bool {
+        self.size.is_some() || self.cols.is_some() || self.rows.is_some()
     }
 }
 
-impl<W: Write> DerefMut for HideCursor<W> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum SyncOutput {
+    Auto,
+    On,
+    Off,
+}
+
+impl SyncOutput {
+    fn enabled(self) -> bool {
+        match self {
+            Self::Auto => term::supports_synchronized_output(),
+            Self::On => true,
+            Self::Off => false,
+        }
     }
 }
 
-fn print_dropped_frames(counter: &FrameCounter, mut write: impl Write) {
-    write!(write, "\n\n\n\x1b[0m{counter}").unwrap();
+/// How far into the video the thumbnail is taken from when no time is given
+const THUMBNAIL_FRACTION: f64 = 0.1;
+
+/// The path that reads the video from stdin
+const STDIN: &str = "-";
+
+/// A pipe, a camera, an rtsp stream, or the screen can't seek, so the features that need to seek can't be used with them.
+/// `source` says where the video comes from in the error
+fn check_live(args: &Args, source: &str) -> Result<(), Box<dyn Error>> {
+    let unsupported = [
+        (args.video.len() > 1, "a playlist"),
+        (args.start_at.is_some(), "--start-at"),
+        (
+            matches!(args.thumbnail, Some(Some(_))),
+            "--thumbnail with a time",
+        ),
+        (args.looping || args.loop_count.is_some(), "looping"),
+        (args.speed != 1., "--speed"),
+    ];
+    match unsupported.iter().find(|(used, _)| *used) {
+        Some((_, feature)) => Err(format!("{feature} can't be used when {source}").into()),
+        None => Ok(()),
+    }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
-    let file = if args.url {
-        args.video
-    } else {
-        // gstreamer expects a url like this
-        format!(
-            "file://{}",
-            PathBuf::from(args.video).canonicalize()?.display()
-        )
+/// The gstreamer source for a camera. `device` is passed through as-is, so it's whatever the platform's source
+/// uses to pick a camera
+fn camera_source(device: Option<&str>) -> String {
+    #[cfg(target_os = "linux")]
+    let (element, property) = ("v4l2src", "device");
+    #[cfg(target_os = "macos")]
+    let (element, property) = ("avfvideosrc", "device-index");
+    #[cfg(windows)]
+    let (element, property) = ("ksvideosrc", "device-index");
+    #[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+    let (element, property) = ("autovideosrc", "");
+    match device {
+        Some(device) if !property.is_empty() => format!("{element} {property}={device}"),
+        _ => element.to_owned(),
+    }
+}
+
+/// The gstreamer source for the whole screen.
+/// A screen is a lot bigger than the terminal, so the source is told to only capture as many frames as we'll draw, and
+/// a leaky queue throws away frames that videoscale hasn't gotten to yet instead of letting them pile up
+fn screen_source(fps: Option<u32>) -> String {
+    #[cfg(target_os = "macos")]
+    let element = "avfvideosrc capture-screen=true";
+    #[cfg(windows)]
+    let element = "d3d11screencapturesrc";
+    #[cfg(not(any(target_os = "macos", windows)))]
+    let element = "ximagesrc use-damage=false";
+    let rate = match fps {
+        Some(fps) => format!(" ! video/x-raw,framerate={fps}/1"),
+        None => String::new(),
     };
+    format!("{element}{rate} ! queue leaky=downstream max-size-buffers=1")
+}
 
-    let termsize = termsize::get().unwrap();
-    let (termwidth, termheight) = (termsize.cols, termsize.rows);
+/// Whether the video is an rtsp url, which is live and can't seek
+fn is_rtsp(video: &str) -> bool {
+    ["rtsp://", "rtsps://", "rtspt://"]
+        .iter()
+        .any(|scheme| video.starts_with(scheme))
+}
 
-    let out = BufWriter::with_capacity(
-        (termwidth as usize * termheight as usize) * 18, // have room for slightly above the worst case where we need an escape sequence for each pixel on the screen
-        stdout().lock(),
+/// The extensions of the formats gstreamer can decode as a still image
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "bmp", "webp", "tif", "tiff"];
+
+/// Whether the extension of a path or url is one of `extensions`
+fn has_extension(video: &str, extensions: &[&str]) -> bool {
+    std::path::Path::new(video)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extensions
+                .iter()
+                .any(|known| extension.eq_ignore_ascii_case(known))
+        })
+}
+
+/// Guesses whether a path or url is a still image from its extension
+fn is_image(video: &str) -> bool {
+    has_extension(video, IMAGE_EXTENSIONS)
+}
+
+/// Guesses whether a path or url is a gif, where every frame has its own delay that only holds up when the frames are
+/// paced by their timestamps
+fn is_gif(video: &str) -> bool {
+    has_extension(video, &["gif"])
+}
+
+/// What is left on the screen once the video is over
+enum ExitScreen {
+    /// The screen the video was drawn on is gone, or was never drawn on, so the summary can go right where the cursor is
+    Untouched,
+    /// The last frame is still there on a screen this many rows tall
+    LastFrame(u16),
+    /// The last frame is still there, but the user asked for it to be cleared
+    Clear,
+}
+
+fn print_dropped_frames(
+    counter: &FrameCounter,
+    mut write: impl Write,
+    screen: ExitScreen,
+    json: bool,
+) -> std::io::Result<()> {
+    match screen {
+        ExitScreen::Untouched => (),
+        // the cursor could be anywhere on the frame, so go below it before printing
+        ExitScreen::LastFrame(rows) => writeln!(write, "\x1b[{rows};1H")?,
+        ExitScreen::Clear => write!(write, "\x1b[2J\x1b[H")?,
+    }
+    if json {
+        writeln!(write, "{}", counter.to_json())
+    } else {
+        writeln!(write, "{counter}")
+    }
+}
+
+/// Writes the frame stats to `--stats-file` if it was passed
+fn write_stats_file(args: &Args, counter: &FrameCounter) -> std::io::Result<()> {
+    match &args.stats_file {
+        Some(path) => std::fs::write(path, counter.to_json() + "\n"),
+        None => Ok(()),
+    }
+}
+
+/// Prints where the time went with --profile, on standard error so it stays out of --stats-json
+fn print_profile(player: &Player<impl Playback>) {
+    if let Some(profile) = player.profile() {
+        eprintln!("{profile}");
+    }
+}
+
+/// Throws away everything written to it, counting the bytes and the flushes, which are one per frame unless
+/// --no-flush-per-frame is on
+#[derive(Default)]
+struct CountingWriter {
+    bytes: usize,
+    flushes: usize,
+}
+
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.bytes += buf.len();
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.flushes += 1;
+        Ok(())
+    }
+}
+
+/// Plays the whole video into a [`CountingWriter`] and prints the numbers
+fn bench(args: &Args, player: &mut Player<impl Playback>) -> Result<(), Box<dyn Error>> {
+    let mut out = CountingWriter::default();
+    let start = Instant::now();
+    player.run(&mut out)?;
+    let elapsed = start.elapsed().as_secs_f64();
+    let frames = player.frames_drawn();
+    println!(
+        "rendered {frames} frames in {elapsed:.2}s ({:.1} fps)",
+        frames as f64 / elapsed
+    );
+    if args.no_flush_per_frame {
+        println!("flushed {} times", out.flushes);
+    }
+    println!(
+        "wrote {} bytes ({:.0} bytes per frame)",
+        out.bytes,
+        out.bytes as f64 / frames.max(1) as f64
     );
-    let mut out = HideCursor::new(out);
+    let counter = player.counter();
+    if args.stats_json {
+        println!("{}", counter.to_json());
+    } else {
+        println!("{counter}");
+    }
+    write_stats_file(args, &counter)?;
+    print_profile(player);
+    Ok(())
+}
 
-    write!(out, "\x1b[2J")?; // clear the screen
-    gst::init()?;
+/// Plays back a recording on the alternate screen until it ends or the user presses ctrl+c
+fn replay(args: &Args, path: &Path) -> Result<(), Box<dyn Error>> {
+    let recording = BufReader::new(File::open(path)?);
+    let interrupt = term::interrupt_flag()?;
 
-    // Resize with half the height because the terminal font is generally ~1:2 aspect ratio.
-    // Use rgbx format because we will use the `x` to store the character printed.
-    // Except kitty just wants either rgb or rgba, so we will opt into the latter
-    let (params, format) = if !args.kitty {
-        (
-            format!("width={termwidth},height={termheight},pixel-aspect-ratio=1/2"),
-            "RGBx",
-        )
+    let mut out = HideCursor::new(AltScreen::new(stdout().lock(), !args.no_altscreen), true);
+    if let Err(e) = record::replay(recording, &mut *out, || interrupt.load(Ordering::Relaxed)) {
+        drop(out);
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+    out.leave()?;
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    if let Some(path) = &args.replay {
+        return replay(&args, path);
+    }
+    if args.video.iter().any(|video| video == STDIN) {
+        check_live(&args, "reading from stdin")?;
+    }
+    if args.camera.is_some() {
+        check_live(&args, "playing from a camera")?;
+    }
+    if args.video.iter().any(|video| is_rtsp(video)) {
+        check_live(&args, "playing an rtsp stream")?;
+    }
+    if args.screen {
+        check_live(&args, "capturing the screen")?;
+    }
+    let playlist = args
+        .video
+        .iter()
+        .map(|video| {
+            Ok(if video == STDIN {
+                // fdsrc handles fd:// uris, so playbin can read from stdin like any other source
+                "fd://0".to_owned()
+            } else if args.url || is_rtsp(video) {
+                video.clone()
+            } else {
+                // gstreamer expects a url like this
+                format!("file://{}", PathBuf::from(video).canonicalize()?.display())
+            })
+        })
+        .collect::<Result<Vec<_>, std::io::Error>>()?;
+
+    let (termwidth, termheight) = match (args.size, args.cols, args.rows) {
+        (Some(size), ..) => size,
+        (None, Some(cols), Some(rows)) => (cols, rows),
+        (None, cols, rows) => {
+            let (width, height) = termsize::get()
+                .map(|size| (size.cols, size.rows))
+                .unwrap_or_else(|| {
+                    let (width, height) = FALLBACK_SIZE;
+                    eprintln!("warning: couldn't get the size of the terminal, so the video will be {width}x{height}. Pass --size to pick another size");
+                    FALLBACK_SIZE
+                });
+            (cols.unwrap_or(width), rows.unwrap_or(height))
+        }
+    };
+
+    let dump_frame = match args.dump_frame.as_deref() {
+        Some([n, path]) => match n.parse::<u64>() {
+            Ok(n) if n > 0 => Some((n, PathBuf::from(path))),
+            _ => return Err(format!("`{n}` is not a frame number, expected 1 or more").into()),
+        },
+        _ => None,
+    };
+    let scale = if args.keep_aspect {
+        Scale::Fit
     } else {
-        ("pixel-aspect-ratio=1/1".to_owned(), "RGBA")
+        args.scale
     };
-    let mut producer = producer::GstProducer::new(
-        &format!(
-            "playbin uri=\"{file}\" video-sink=\"videoconvert
-        ! videoscale 
-        ! appsink name=app_sink caps=video/x-raw,{params},format={format}
-        ! sink_to_location\"",
-        ),
-        Duration::from_secs(args.timeout),
-    )?;
-
-    let wait = &producer.subscribe();
-    let o = &mut *out;
-    match (args.kitty, args.ansi256, args.background) {
-        // kitty
-        (true, _, _) => {
-            o.write_all(b"\x1b[0;0H")?;
-            do_run::<KittyRenderer>(wait, &producer, o)?;
+    if args.pad_color.is_some() && scale != Scale::Fit {
+        return Err("--pad-color only applies with --scale fit or --keep-aspect".into());
+    }
+
+    // an image would end after its only frame, so keep repeating it until the user quits.
+    // this also lets it be redrawn at the new size when the terminal is resized
+    let image =
+        args.image || (!playlist.is_empty() && args.video.iter().all(|video| is_image(video)));
+    // these can take a long time to deliver the next frame without anything being wrong
+    let live = args.url
+        || args.camera.is_some()
+        || args.screen
+        || args
+            .video
+            .iter()
+            .any(|video| video == STDIN || is_rtsp(video));
+    let source = match &args.camera {
+        Some(device) => Source::Live(camera_source(device.as_deref())),
+        None if args.screen => Source::Live(screen_source(args.fps)),
+        None => Source::Playbin {
+            playlist,
+            filter: image.then(|| "imagefreeze".to_owned()),
+        },
+    };
+    let mode = if args.sixel {
+        Mode::Sixel
+    } else if args.iterm2 {
+        Mode::ITerm2
+    } else if args.half_block {
+        Mode::HalfBlock
+    } else if args.quadrant {
+        Mode::Quadrant
+    } else if args.sextant {
+        Mode::Sextant
+    } else if args.kitty {
+        Mode::Kitty
+    } else if args.no_color {
+        Mode::NoColor
+    } else if args.yuv {
+        Mode::Luma
+    } else {
+        Mode::Cells {
+            // the palette colors are written as truecolor
+            colors: if args.palette.is_some() {
+                Colors::Truecolor
+            } else if args.ansi256 {
+                Colors::Ansi256
+            } else {
+                args.colors
+            },
+            grayscale: args.grayscale,
+            background: args.background,
+        }
+    };
+    let detected = detect_colors(
+        std::env::var("COLORTERM").ok().as_deref(),
+        std::env::var("TERM").ok().as_deref(),
+    );
+    // nothing gets drawn to the terminal with --probe and --bench
+    let drawn = !args.probe && !args.bench;
+    if drawn && mode.needs_truecolor() && detected != Colors::Truecolor && !args.force_truecolor {
+        eprintln!("warning: the terminal doesn't say it supports truecolor, so the colors may come out wrong. Pass --colors 256 or --colors 16 if they do, or --force-truecolor to hide this warning");
+    }
+    let palette = match &args.palette {
+        Some(path) => {
+            let text = std::fs::read_to_string(path)
+                .map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+            let palette =
+                Palette::parse(&text).map_err(|err| format!("{}: {err}", path.display()))?;
+            Some(Arc::new(palette))
+        }
+        None => None,
+    };
+    let subtitles = match &args.sub_file {
+        Some(path) => {
+            // plenty of subtitle files aren't utf-8, and a few wrong characters beat not showing them at all
+            let cues = subtitle::parse_srt(&String::from_utf8_lossy(&std::fs::read(path)?));
+            if cues.is_empty() {
+                return Err(format!("{} has no subtitles in it", path.display()).into());
+            }
+            Some(Subtitles::Cues(cues))
         }
-        // ansi + background
-        (_, true, true) => do_run::<DefaultRenderer<BackgroundAnsi256>>(wait, &producer, o)?,
-        // ansi + not background
-        (_, true, false) => do_run::<DefaultRenderer<Ansi256>>(wait, &producer, o)?,
-        // rgb + background
-        (_, false, true) => do_run::<DefaultRenderer<BackgroundRgb>>(wait, &producer, o)?,
-        // rgb + not background
-        (_, false, false) => do_run::<DefaultRenderer<Rgb>>(wait, &producer, o)?,
+        None => args.subtitles.map(|_| Subtitles::Embedded),
+    };
+    let config = PlayConfig {
+        source,
+        producer: ProducerOptions {
+            timeout: Duration::from_secs(args.timeout),
+            decoder: args.decoder,
+            repeat: match (args.looping, args.loop_count) {
+                (true, _) => Repeat::Forever,
+                (false, Some(count)) => Repeat::Times(count - 1),
+                (false, None) => Repeat::Never,
+            },
+            verbose: args.verbose,
+            start_at: match args.thumbnail {
+                Some(Some(time)) => Some(StartAt::Time(time)),
+                Some(None) => Some(StartAt::Fraction(THUMBNAIL_FRACTION)),
+                None => args.start_at.map(StartAt::Time),
+            },
+            // probing only needs the pipeline prerolled
+            rate: if args.probe { 0. } else { args.speed },
+            buffer: args.buffer as usize,
+            buffer_duration: args.buffer_duration.map(Duration::from_secs_f64),
+            rtsp_latency: args.rtsp_latency,
+            rtsp_tcp: args.rtsp_tcp,
+            subtitles: args.subtitles,
+            max_bad_samples: args.max_bad_samples,
+        },
+        render: RenderOptions {
+            brightness: args.brightness,
+            contrast: args.contrast,
+            gamma: args.gamma,
+            invert: args.invert,
+            use_rep: args.use_rep,
+            compress: !args.kitty_no_compress,
+            kitty_chunk: args.kitty_chunk,
+            dither: args.dither,
+            temporal_dither: args.temporal_dither,
+            luma_weights: args.luma_weights.unwrap_or_default(),
+            black_threshold: args.black_threshold,
+            color_tolerance: args.color_tolerance,
+            motion_threshold: args.motion_threshold,
+            edges: match (args.edges_mono, args.edges) {
+                (true, _) => Edges::Mono,
+                (false, true) => Edges::Colored,
+                (false, false) => Edges::Off,
+            },
+            palette,
+            ..Default::default()
+        },
+        mode,
+        no_convert: args.no_convert,
+        sync: args.sync.enabled(),
+        stats: args.stats,
+        progress: args.progress,
+        subtitles,
+        banner: args.banner.clone().map(|text| Banner {
+            text,
+            position: args.banner_pos,
+        }),
+        stall_timeout: match args.stall_timeout {
+            Some(0.) => None,
+            Some(secs) => Some(Duration::from_secs_f64(secs)),
+            None if live => None,
+            None => Some(DEFAULT_STALL_TIMEOUT),
+        },
+        scale,
+        pad_color: args.pad_color,
+        scale_method: args.scale_method,
+        squash: args.squash,
+        max_fps: args.fps,
+        pace: args.pace || (!args.batch && args.video.iter().any(|video| is_gif(video))),
+        batch: args.batch,
+        term_size: (termwidth, termheight),
+        cell_aspect: args.cell_aspect,
+        cell_px: args.cell_px,
+        interactive: args.thumbnail.is_none(),
+        // the terminal being watched is this one, which isn't the one being drawn on with --output
+        follow_resize: !args.fixed_size() && args.thumbnail.is_none() && args.output.is_none(),
+        single_frame: args.thumbnail.is_some(),
+        flush_interval: args.no_flush_per_frame.then_some(BATCHED_FLUSH_INTERVAL),
+        dump_frame,
+        profile: args.profile,
+    };
+    if config.video_rows(termheight) == 0 {
+        return Err(format!(
+            "a terminal {termheight} rows tall has no room for the video next to the overlays, pass a bigger --size or fewer of --stats, --progress, --sub-file, and --banner"
+        )
+        .into());
+    }
+    if let Some(dir) = &args.frames_dir {
+        let interval = Duration::from_secs_f64(1. / args.fps.unwrap_or(FRAMES_DIR_FPS) as f64);
+        let size = config
+            .video_size(config.term_size)
+            .ok_or("--frames-dir only works with the modes drawn with characters")?;
+        let producer = DirProducer::new(dir, interval, size, args.buffer as usize)?;
+        return start(
+            &args,
+            Player::with_producer(config, producer),
+            (termwidth, termheight),
+        );
+    }
+    let player = Player::new(config)?;
+    if args.probe {
+        println!("{}", player.probe());
+        return Ok(());
+    }
+    start(&args, player, (termwidth, termheight))
+}
+
+/// Plays the video with `player`, or benchmarks it with --bench
+fn start<P: Playback + Send>(
+    args: &Args,
+    mut player: Player<P>,
+    (termwidth, termheight): (u16, u16),
+) -> Result<(), Box<dyn Error>> {
+    if args.bench {
+        return bench(args, &mut player);
     }
 
-    print_dropped_frames(&producer.counter(), &mut *out);
+    // draw on a thread of its own, so the signal handlers belong to this one instead of the render loop, which only
+    // has to check the flag they set
+    if args.thumbnail.is_none() {
+        player.set_interrupt(term::interrupt_flag()?);
+    }
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| draw(args, &mut player, (termwidth, termheight)).map_err(|e| e.to_string()))
+            .join()
+            .expect("the render thread shouldn't panic")
+    })?;
     Ok(())
 }
 
-fn do_run<R: Renderer>(
-    wait: &Receiver<ProducerMessage>,
-    producer: &GstProducer,
-    mut out: impl Write,
-) -> Result<(), Box<dyn Error>>
-where
-{
-    let mut renderer = None;
-    let mut state = None;
-    let interrupt = std::sync::Arc::new(AtomicBool::new(false));
-    let i = interrupt.clone();
-    ctrlc::set_handler(move || i.store(true, std::sync::atomic::Ordering::Relaxed))
-        .expect("failed to set interrupt handler");
-    while let Ok(msg) = wait.recv_timeout(Duration::from_secs(3)) {
-        if interrupt.load(std::sync::atomic::Ordering::Relaxed) {
-            break;
+/// Sets up the terminal, draws the video into it, and prints the summary once it's over
+fn draw(
+    args: &Args,
+    player: &mut Player<impl Playback>,
+    (termwidth, termheight): (u16, u16),
+) -> Result<(), Box<dyn Error>> {
+    let recording = match &args.record {
+        Some(path) => Some(BufWriter::new(File::create(path)?)),
+        None => None,
+    };
+    let cast = match &args.cast {
+        Some(path) => Some(BufWriter::new(File::create(path)?)),
+        None => None,
+    };
+
+    let (target, is_terminal): (Box<dyn Write>, bool) = match &args.output {
+        Some(path) => {
+            // truncating only does something to a regular file, and leaves ptys and named pipes alone
+            let file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(path)?;
+            let is_terminal = file.is_terminal();
+            (Box::new(file), is_terminal)
         }
-        match msg {
-            ProducerMessage::Initialize { width, height } => {
-                let r = R::from_dims(width, height);
+        None => (Box::new(stdout().lock()), stdout().is_terminal()),
+    };
+    // set up the terminal after the producer so its errors and warnings end up on the normal screen.
+    // files and pipes get the same buffer, since flushing once per frame is what keeps them from tearing too
+    let out = BufWriter::with_capacity(
+        (termwidth as usize * termheight as usize) * 18, // have room for slightly above the worst case where we need an escape sequence for each pixel on the screen
+        target,
+    );
+    // clicks on the progress bar seek, and turning the reporting off again is left to the drop like the rest
+    let out = MouseReporting::new(
+        out,
+        is_terminal && args.progress && args.thumbnail.is_none(),
+    );
+    // a thumbnail has to stay on the normal screen to be any use
+    let altscreen = is_terminal && !args.no_altscreen && args.thumbnail.is_none();
+    let mut out = HideCursor::new(AltScreen::new(out, altscreen), is_terminal);
 
-                state = Some(r.create_state());
-                renderer = Some(r);
-            }
-            ProducerMessage::FrameReady => {
-                let r = renderer.as_mut().expect("renderer should be initialized");
-                let state = state.as_mut().expect("differ should be initialized");
-                {
-                    let frame = producer.frame().expect("frame should be ready");
-                    let frame = r.verify_input(&frame);
-                    r.consume(frame);
-                }
-                r.render_frame(&mut out, state)?;
+    let result = (|| {
+        let mut output: Box<dyn Write> = Box::new(&mut *out);
+        if let Some(file) = recording {
+            output = Box::new(Recorder::new(output, file)?);
+        }
+        match cast {
+            Some(file) => {
+                let cast = CastWriter::new(output, file, (termwidth, termheight))?;
+                let resizer = cast.resizer();
+                player.run_with_resize(cast, resizer)
             }
+            None => player.run(output),
         }
+    })();
+    if let Err(e) = result {
+        // restore the terminal before printing the error so it doesn't get lost in the video
+        drop(out);
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+    if args.thumbnail.is_some() {
+        // put the prompt under the thumbnail instead of in the middle of it
+        writeln!(out, "\x1b[{termheight};1H\x1b[0m")?;
+        out.flush()?;
+        return Ok(());
     }
+    // the colors of the last frame are reset with the rest of what was drawn, so nothing comes before the summary
+    // when it's on its own, like the json going to stdout with --output
+    write!(out, "\x1b[0m")?;
+    // leave the alternate screen first so the summary stays in the normal buffer
+    out.leave()?;
+    let counter = player.counter();
+    if args.output.is_some() {
+        // the summary is for whoever ran onna, not for whatever is reading the output
+        drop(out);
+        print_dropped_frames(&counter, stdout(), ExitScreen::Untouched, args.stats_json)?;
+        write_stats_file(args, &counter)?;
+        print_profile(player);
+        return Ok(());
+    }
+    let screen = match (altscreen, args.clear_on_exit) {
+        (true, _) => ExitScreen::Untouched,
+        (false, true) => ExitScreen::Clear,
+        // the terminal could have been resized since the start, and the frame followed it
+        (false, false) => ExitScreen::LastFrame(if args.fixed_size() {
+            termheight
+        } else {
+            termsize::get().map_or(termheight, |size| size.rows)
+        }),
+    };
+    print_dropped_frames(&counter, &mut *out, screen, args.stats_json)?;
+    write_stats_file(args, &counter)?;
+    // the summary goes first
+    out.flush()?;
+    print_profile(player);
     Ok(())
 }
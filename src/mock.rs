@@ -0,0 +1,104 @@
+use std::{
+    sync::{
+        atomic::Ordering,
+        mpsc::{sync_channel, Receiver},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use crate::producer::FrameCounter;
+use crate::{Playback, Producer, ProducerMessage};
+
+/// One step of the script a [`MockProducer`] plays
+#[derive(Debug, Clone)]
+pub enum MockEvent {
+    /// Frames are this many pixels wide and tall from now on, like after a resize
+    Resize { width: u32, height: u32 },
+    /// An RGBx frame at the size from the last `Resize`, shown at `pts` if there is one
+    Frame {
+        data: Vec<u8>,
+        pts: Option<Duration>,
+    },
+}
+
+/// A [`Producer`] that sends a script of frames from memory, so renderers and the diffing can be driven and checked
+/// byte for byte without gstreamer or a video.
+///
+/// Every message is queued up as soon as [`subscribe`](Producer::subscribe) is called, followed by an `Eos`, and each
+/// frame gets a slot to itself, so nothing is ever dropped
+pub struct MockProducer {
+    script: Vec<MockEvent>,
+    frames: Vec<Mutex<Vec<u8>>>,
+    counter: Arc<FrameCounter>,
+}
+
+impl MockProducer {
+    pub fn new(script: Vec<MockEvent>) -> Self {
+        Self {
+            script,
+            frames: Vec::new(),
+            counter: Arc::default(),
+        }
+    }
+    /// A producer for `frames` that are all `(width, height)` pixels
+    pub fn from_frames(width: u32, height: u32, frames: impl IntoIterator<Item = Vec<u8>>) -> Self {
+        let frames = frames
+            .into_iter()
+            .map(|data| MockEvent::Frame { data, pts: None });
+        Self::new(
+            std::iter::once(MockEvent::Resize { width, height })
+                .chain(frames)
+                .collect(),
+        )
+    }
+}
+
+impl Producer for MockProducer {
+    fn subscribe(&mut self) -> Receiver<ProducerMessage> {
+        let (notify, recv) = sync_channel(self.script.len() + 1);
+        self.frames.clear();
+        for event in &self.script {
+            let message = match event {
+                MockEvent::Resize { width, height } => ProducerMessage::Initialize {
+                    width: *width,
+                    height: *height,
+                },
+                MockEvent::Frame { data, pts } => {
+                    self.frames.push(Mutex::new(data.clone()));
+                    self.counter.not_dropped.fetch_add(1, Ordering::SeqCst);
+                    ProducerMessage::FrameReady {
+                        slot: self.frames.len() - 1,
+                        pts: *pts,
+                    }
+                }
+            };
+            notify
+                .send(message)
+                .expect("the channel has room for the whole script");
+        }
+        notify
+            .send(ProducerMessage::Eos)
+            .expect("the channel has room for the end of the script");
+        recv
+    }
+    fn swap_frame(&self, slot: usize, frame: &mut Vec<u8>) -> bool {
+        match self.frames.get(slot) {
+            Some(data) => {
+                std::mem::swap(&mut *data.lock().unwrap(), frame);
+                true
+            }
+            None => false,
+        }
+    }
+    // the whole script is sent up front
+    fn is_finished(&self) -> bool {
+        true
+    }
+}
+
+impl Playback for MockProducer {
+    fn counter(&self) -> Arc<FrameCounter> {
+        self.counter.clone()
+    }
+}
@@ -0,0 +1,292 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use crate::producer::FrameCounter;
+
+/// The number of frames the framerate is averaged over
+const FPS_WINDOW: usize = 30;
+
+/// A line of playback statistics drawn on the top row of the terminal
+pub struct StatsOverlay {
+    timestamps: VecDeque<Instant>,
+}
+
+impl Default for StatsOverlay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StatsOverlay {
+    pub fn new() -> Self {
+        Self {
+            timestamps: VecDeque::with_capacity(FPS_WINDOW),
+        }
+    }
+    pub fn record_frame(&mut self) {
+        if self.timestamps.len() == FPS_WINDOW {
+            self.timestamps.pop_front();
+        }
+        self.timestamps.push_back(Instant::now());
+    }
+    /// The moving average of the framerate over the last few frames
+    pub fn fps(&self) -> f32 {
+        match (self.timestamps.front(), self.timestamps.back()) {
+            (Some(first), Some(last)) if first != last => {
+                (self.timestamps.len() - 1) as f32 / (*last - *first).as_secs_f32()
+            }
+            _ => 0.,
+        }
+    }
+    /// `buffering` is how full the network buffer is while playback waits for it
+    pub fn write(
+        &self,
+        counter: &FrameCounter,
+        buffering: Option<u8>,
+        (width, height): (u16, u16),
+        output: &mut impl Write,
+    ) -> io::Result<()> {
+        let mut line = format!(
+            "{:.1} fps | {:.2}% dropped | {width}x{height}",
+            self.fps(),
+            counter.dropped_percentage()
+        );
+        let bad = counter.bad.load(std::sync::atomic::Ordering::SeqCst);
+        if bad > 0 {
+            line += &format!(" | {bad} bad");
+        }
+        if let Some(percent) = buffering {
+            line += &format!(" | buffering {percent}%");
+        }
+        // reset the colors, draw the line, and clear whatever is left of the row
+        write!(output, "\x1b[1;1H\x1b[0m")?;
+        output.write_all(&line.as_bytes()[..line.len().min(width as usize)])?;
+        output.write_all(b"\x1b[K")
+    }
+}
+
+/// How often the progress bar is redrawn, since it barely changes between frames
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Where the bar was last drawn, so a click on it can be turned into a time in the video
+struct BarLayout {
+    row: u16,
+    // 1-based like the mouse reports
+    columns: Range<u16>,
+    duration: Duration,
+}
+
+/// A progress bar drawn on the bottom row of the terminal
+pub struct ProgressBar {
+    last_drawn: Option<Instant>,
+    layout: Option<BarLayout>,
+}
+
+impl Default for ProgressBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgressBar {
+    pub fn new() -> Self {
+        Self {
+            last_drawn: None,
+            layout: None,
+        }
+    }
+    /// Whether enough time has passed since the bar was last drawn to draw it again
+    pub fn due(&self) -> bool {
+        self.last_drawn
+            .is_none_or(|last| last.elapsed() >= PROGRESS_INTERVAL)
+    }
+    /// Draws the bar, or just the elapsed time if the duration is unknown like in live streams
+    pub fn write(
+        &mut self,
+        position: Option<Duration>,
+        duration: Option<Duration>,
+        (width, height): (u16, u16),
+        output: &mut impl Write,
+    ) -> io::Result<()> {
+        self.last_drawn = Some(Instant::now());
+        let position = position.unwrap_or(Duration::ZERO);
+        let line = match duration.filter(|d| !d.is_zero()) {
+            Some(duration) => {
+                let (elapsed, total) = (format_time(position), format_time(duration));
+                // the two times, the brackets, and the spaces between them
+                let bar_width = (width as usize).saturating_sub(elapsed.len() + total.len() + 4);
+                let filled = ((position.as_secs_f64() / duration.as_secs_f64()).min(1.)
+                    * bar_width as f64) as usize;
+                // the bar starts after the elapsed time, a space, and the bracket
+                let start = elapsed.len() as u16 + 3;
+                self.layout = Some(BarLayout {
+                    row: height,
+                    columns: start..start + bar_width as u16,
+                    duration,
+                });
+                format!(
+                    "{elapsed} [{}{}] {total}",
+                    "=".repeat(filled),
+                    " ".repeat(bar_width - filled)
+                )
+            }
+            None => {
+                self.layout = None;
+                format!("elapsed {}", format_time(position))
+            }
+        };
+        write!(output, "\x1b[{height};1H\x1b[0m")?;
+        output.write_all(&line.as_bytes()[..line.len().min(width as usize)])?;
+        output.write_all(b"\x1b[K")
+    }
+    /// The time in the video at a 1-based `(column, row)` of the terminal, or `None` if that isn't on the bar
+    pub fn time_at(&self, (column, row): (u16, u16)) -> Option<Duration> {
+        let layout = self.layout.as_ref()?;
+        if row != layout.row || !layout.columns.contains(&column) {
+            return None;
+        }
+        let fraction = (column - layout.columns.start) as f64 / layout.columns.len() as f64;
+        Some(layout.duration.mul_f64(fraction))
+    }
+}
+
+/// How long the volume stays on the screen after it was changed
+const VOLUME_DURATION: Duration = Duration::from_secs(1);
+
+/// The number of cells in the volume meter
+const VOLUME_WIDTH: usize = 10;
+
+/// The volume drawn in the top right corner of the terminal for a moment after it changes
+pub struct VolumeIndicator {
+    shown: Option<(f64, Instant)>,
+}
+
+impl Default for VolumeIndicator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VolumeIndicator {
+    pub fn new() -> Self {
+        Self { shown: None }
+    }
+    /// Starts showing `volume`, from 0 to 1
+    pub fn show(&mut self, volume: f64) {
+        self.shown = Some((volume, Instant::now()));
+    }
+    /// Whether the indicator was on the screen and should go away now. This only returns true once, so the video under
+    /// it gets redrawn once
+    pub fn expired(&mut self) -> bool {
+        let expired = self
+            .shown
+            .is_some_and(|(_, shown)| shown.elapsed() >= VOLUME_DURATION);
+        if expired {
+            self.shown = None;
+        }
+        expired
+    }
+    pub fn write(&self, (width, _): (u16, u16), output: &mut impl Write) -> io::Result<()> {
+        let Some((volume, _)) = self.shown else {
+            return Ok(());
+        };
+        let filled = (volume * VOLUME_WIDTH as f64).round() as usize;
+        let line = format!(
+            " volume {:>3}% [{}{}] ",
+            (volume * 100.).round(),
+            "=".repeat(filled),
+            " ".repeat(VOLUME_WIDTH - filled)
+        );
+        let column = (width as usize).saturating_sub(line.len()) + 1;
+        write!(output, "\x1b[1;{column}H\x1b[0m")?;
+        output.write_all(&line.as_bytes()[..line.len().min(width as usize)])
+    }
+}
+
+/// Draws `text` centered on the `rows` of the terminal starting at the 1-based `first_row`, wrapped to the `width` of
+/// the terminal. Whatever was there before is cleared, so an old subtitle goes away when there's no new one
+pub fn write_subtitle(
+    text: Option<&str>,
+    (first_row, rows): (u16, u16),
+    width: u16,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    let lines = text.map_or_else(Vec::new, |text| wrap(text, width as usize));
+    // the last lines are cut off, since the first ones are usually the ones being spoken
+    let mut lines = lines.iter();
+    for row in first_row..first_row + rows {
+        write!(output, "\x1b[{row};1H\x1b[0m\x1b[K")?;
+        if let Some(line) = lines.next() {
+            let column = (width as usize - line.chars().count()) / 2 + 1;
+            write!(output, "\x1b[{row};{column}H{line}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Draws `text` on the 1-based `row`, cut off at the `width` of the terminal. Control characters are left out, so the
+/// text can't move the cursor or change the colors of the rest of the frame
+pub fn write_banner(text: &str, row: u16, width: u16, output: &mut impl Write) -> io::Result<()> {
+    let line: String = text
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(width as usize)
+        .collect();
+    write!(output, "\x1b[{row};1H\x1b[0m{line}\x1b[K")
+}
+
+/// Splits `text` into lines of at most `width` characters, breaking between words where it can
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    if width == 0 {
+        return lines;
+    }
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let mut word = word;
+            // words that don't fit on a line of their own get broken up
+            while word.chars().count() > width {
+                if !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                }
+                let split = word
+                    .char_indices()
+                    .nth(width)
+                    .map_or(word.len(), |(i, _)| i);
+                lines.push(word[..split].to_owned());
+                word = &word[split..];
+            }
+            if word.is_empty() {
+                continue;
+            }
+            let len = line.chars().count();
+            if len > 0 && len + 1 + word.chars().count() > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+/// Formats a duration as MM:SS, or as HH:MM:SS when it is at least an hour long
+pub(crate) fn format_time(time: Duration) -> String {
+    let secs = time.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes:02}:{seconds:02}")
+    }
+}
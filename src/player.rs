@@ -0,0 +1,1461 @@
+use clap::ValueEnum;
+use gstreamer as gst;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::color::{
+    Ansi16, Ansi256, BackgroundAnsi16, BackgroundAnsi256, BackgroundRgb, Colorize, Grayscale,
+    NoColor, Rgb,
+};
+use crate::overlay::{self, ProgressBar, StatsOverlay, VolumeIndicator};
+use crate::producer::{
+    Error, FrameCounter, GstProducer, Producer, ProducerMessage, ProducerOptions, Source,
+    StreamInfo,
+};
+use crate::render::{
+    boxed, DefaultRenderer, HalfBlockRenderer, ITerm2Renderer, KittyRenderer, LumaRenderer,
+    QuadrantRenderer, RenderOptions, Renderer, RendererFactory, SextantRenderer, SixelRenderer,
+};
+use crate::resize_watcher::{self, ResizeWatcher};
+use crate::subtitle::{self, Subtitles};
+use crate::term::{self, Input, Keys, BEGIN_SYNC, END_SYNC};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Colors {
+    Auto,
+    #[value(name = "16")]
+    Ansi16,
+    #[value(name = "256")]
+    Ansi256,
+    Truecolor,
+}
+
+/// How the video is fit to the terminal when their aspect ratios differ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Scale {
+    /// Stretch the video to fill the terminal
+    #[default]
+    Fill,
+    /// Scale the video to the biggest size that fits in the terminal without stretching it, and center it between bars
+    Fit,
+    /// Scale the video to cover the whole terminal without stretching it, and cut off the sides that don't fit
+    Crop,
+}
+
+/// Which edge of the terminal the banner goes on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum BannerPosition {
+    /// Above the video, under the stats overlay
+    Top,
+    /// Under the video and the subtitles, above the progress bar
+    #[default]
+    Bottom,
+}
+
+/// A line of text, like a label for a recording, on a row of its own next to the video
+#[derive(Debug, Clone)]
+pub struct Banner {
+    pub text: String,
+    pub position: BannerPosition,
+}
+
+/// How videoscale picks the color of each pixel it scales to, from the cheapest to the most expensive.
+/// Every frame gets scaled, so the slower methods cost cpu for as long as the video plays
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ScaleMethod {
+    /// The closest pixel, which keeps hard edges
+    Nearest,
+    /// Blends the four closest pixels, which is what videoscale does by default
+    Bilinear,
+    /// Blends the sixteen closest pixels
+    #[value(name = "4-tap")]
+    FourTap,
+    /// The sharpest of them, which is good for photos
+    Lanczos,
+}
+
+impl ScaleMethod {
+    /// The name of the method in videoscale's `method` property
+    fn nick(self) -> &'static str {
+        match self {
+            Self::Nearest => "nearest-neighbour",
+            Self::Bilinear => "bilinear",
+            Self::FourTap => "4-tap",
+            Self::Lanczos => "lanczos",
+        }
+    }
+}
+
+/// Guesses how many colors the terminal supports from the values of $COLORTERM and $TERM
+pub fn detect_colors(colorterm: Option<&str>, term: Option<&str>) -> Colors {
+    let term = term.unwrap_or_default();
+    if matches!(colorterm, Some("truecolor" | "24bit"))
+        || ["truecolor", "24bit", "direct"]
+            .iter()
+            .any(|suffix| term.ends_with(suffix))
+        // these always support truecolor, even over ssh where $COLORTERM gets lost
+        || ["kitty", "alacritty", "foot", "wezterm", "ghostty"]
+            .iter()
+            .any(|name| term.contains(name))
+    {
+        Colors::Truecolor
+    } else if term.contains("256color") {
+        Colors::Ansi256
+    } else {
+        Colors::Ansi16
+    }
+}
+
+/// How the frames are drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// A colored character per cell
+    Cells {
+        /// `Colors::Auto` is detected from the environment when the video starts
+        colors: Colors,
+        grayscale: bool,
+        /// Color the background of each cell instead of the character
+        background: bool,
+    },
+    /// Just the characters without any color escapes, for files and terminals without color
+    NoColor,
+    /// Like `NoColor`, but the decoder hands over `I420` or `NV12` and the characters come straight from the luminance
+    /// plane, so the frames are never converted to RGB
+    Luma,
+    /// Two pixels per cell with half blocks
+    HalfBlock,
+    /// A 2x2 grid of pixels per cell with quadrant blocks
+    Quadrant,
+    /// A 2x3 grid of pixels per cell with sextant blocks, which need a font with the Symbols for Legacy Computing
+    Sextant,
+    Kitty,
+    Sixel,
+    ITerm2,
+}
+
+/// Everything needed to play a video
+#[derive(Debug, Clone)]
+pub struct PlayConfig {
+    pub source: Source,
+    pub producer: ProducerOptions,
+    /// The `row_offset` and `cells` are worked out from the terminal size and the overlays, so they are ignored
+    pub render: RenderOptions,
+    pub mode: Mode,
+    /// Don't convert the decoded video's pixel format, which fails when the decoder can't output the format we need
+    pub no_convert: bool,
+    /// Wrap each frame in the synchronized output escapes
+    pub sync: bool,
+    /// Show the stats overlay on the top row
+    pub stats: bool,
+    /// Show the progress bar on the bottom row
+    pub progress: bool,
+    /// Show subtitles on their own rows under the video
+    pub subtitles: Option<Subtitles>,
+    /// Show a banner on its own row, cut off at the width of the terminal
+    pub banner: Option<Banner>,
+    /// How the video is fit to the terminal
+    pub scale: Scale,
+    /// The color of the bars around the video with [`Scale::Fit`], or the terminal's background if `None`
+    pub pad_color: Option<[u8; 3]>,
+    /// How the frames are scaled to the terminal, or videoscale's default if `None`
+    pub scale_method: Option<ScaleMethod>,
+    /// Scale the frames to twice the rows and average each pair of them into one, which keeps more detail than
+    /// leaving the whole scale to videoscale. Only for [`Mode::Cells`] and [`Mode::NoColor`]
+    pub squash: bool,
+    /// How long to wait for a frame before giving up on a video that stalled, or forever if `None`.
+    /// The end of the video is noticed right away regardless
+    pub stall_timeout: Option<Duration>,
+    /// Render at most this many frames per second
+    pub max_fps: Option<u32>,
+    /// Show each frame when its timestamp says to instead of as soon as it's decoded, skipping the ones that are late
+    pub pace: bool,
+    /// Draw only the newest of the frames that piled up while the last one was drawn, and hold a frame back until
+    /// `max_fps` allows it instead of skipping it. The skipped frames still show up in the diff against the last
+    /// frame that was drawn, so a slow terminal falls behind less than when whole frames are dropped
+    pub batch: bool,
+    /// The size of the terminal as (columns, rows)
+    pub term_size: (u16, u16),
+    /// The width and height of a terminal cell relative to each other, which is usually about 1:2.
+    /// The image protocols draw in real pixels, so they don't need it
+    pub cell_aspect: (u32, u32),
+    /// The size of a terminal cell in pixels for kitty, sixel, and iterm2, for terminals that don't report their size
+    /// in pixels or report it wrong. `None` asks the terminal
+    pub cell_px: Option<(u16, u16)>,
+    /// Read the keybindings from stdin and stop on ctrl+c.
+    /// Turn this off when the output isn't going to the terminal
+    pub interactive: bool,
+    /// Follow the size of the terminal when it's resized instead of sticking to `term_size`
+    pub follow_resize: bool,
+    /// Stop after the first frame is drawn, like for a thumbnail
+    pub single_frame: bool,
+    /// Let the output of several frames pile up and flush it at most this often instead of after every frame, which
+    /// saves a syscall per frame when the frames come faster than the terminal needs them. [`Recorder`](crate::record::Recorder) and
+    /// [`CastWriter`](crate::record::CastWriter) time each frame by its flush, so this shouldn't be used with them. The
+    /// output still goes out as soon as playback has to wait for a frame or stops, so keys and ctrl+c aren't held up
+    /// behind it. `None` flushes every frame
+    pub flush_interval: Option<Duration>,
+    /// Time how long each frame takes to consume, render, and write out, for [`Player::profile`]
+    pub profile: bool,
+    /// Save the frame with this number, counting from 1 and including the frames that are skipped instead of drawn, to a
+    /// PNG at the path exactly as it came from the decoder
+    pub dump_frame: Option<(u64, PathBuf)>,
+}
+
+impl PlayConfig {
+    /// The number of terminal rows left for the video, which is 0 when the overlays don't leave any.
+    /// The stats overlay, progress bar, subtitles, and banner get their own rows so they don't fight with the video
+    pub fn video_rows(&self, termheight: u16) -> u16 {
+        termheight
+            .saturating_sub(self.stats as u16)
+            .saturating_sub(self.progress as u16)
+            .saturating_sub(self.subtitle_rows())
+            .saturating_sub(self.banner.is_some() as u16)
+    }
+
+    /// Whether there's a banner on the `position` edge
+    fn banner_at(&self, position: BannerPosition) -> bool {
+        self.banner
+            .as_ref()
+            .is_some_and(|banner| banner.position == position)
+    }
+
+    /// The number of rows above the video
+    fn top_rows(&self) -> u16 {
+        self.stats as u16 + self.banner_at(BannerPosition::Top) as u16
+    }
+
+    /// The number of terminal rows between the video and the progress bar that are kept for the subtitles
+    fn subtitle_rows(&self) -> u16 {
+        if self.subtitles.is_some() {
+            SUBTITLE_ROWS
+        } else {
+            0
+        }
+    }
+
+    /// How many pixels across and down each cell is drawn from with the modes drawn with characters
+    fn cell_pixels(&self) -> (u32, u32) {
+        match self.mode {
+            // each cell is two pixels stacked on top of each other, which are half as tall as the cell
+            Mode::HalfBlock => (1, 2),
+            // each cell is 2x2 pixels, which are the same shape as the cell
+            Mode::Quadrant => (2, 2),
+            // each cell is 2x3 pixels, which are a third as tall and half as wide as the cell
+            Mode::Sextant => (2, 3),
+            // squashed frames have two pixels per cell stacked like half blocks, until they're averaged
+            _ => (1, self.squashes() as u32 + 1),
+        }
+    }
+
+    /// The size in pixels the frames are scaled to with the modes drawn with characters
+    fn cells_size(&self, (termwidth, termheight): (u16, u16)) -> (u32, u32) {
+        let (across, down) = self.cell_pixels();
+        (
+            termwidth as u32 * across,
+            self.video_rows(termheight) as u32 * down,
+        )
+    }
+
+    /// The size in pixels the frames are scaled to for a terminal of `size`, which with [`Scale::Fit`] is the most
+    /// they can take up. `None` for the image protocols when the size of the terminal in pixels isn't known, which
+    /// leaves the frames at their own size
+    pub fn video_size(&self, (termwidth, termheight): (u16, u16)) -> Option<(u32, u32)> {
+        match self.mode {
+            Mode::Kitty | Mode::Sixel | Mode::ITerm2 => {
+                // these are drawn in real pixels. leave off the last row for sixel, since drawing on it scrolls the screen
+                let rows = self
+                    .video_rows(termheight)
+                    .saturating_sub((self.mode == Mode::Sixel) as u16);
+                let (width, height) = self.pixel_size((termwidth, termheight))?;
+                Some((width, height * rows as u32 / termheight as u32))
+            }
+            _ => Some(self.cells_size((termwidth, termheight))),
+        }
+    }
+
+    /// The caps for the appsink that scale the video to fit in the terminal
+    fn video_caps(&self, size: (u16, u16)) -> String {
+        // Resize to the shape of the cells, since the terminal font is generally ~1:2 aspect ratio.
+        // Use rgbx format because we will use the `x` to store the character printed.
+        // Except kitty just wants either rgb or rgba, so we will opt into the latter.
+        // The luma mode only reads the first plane of whichever yuv format is cheaper to get to
+        let (params, format) = match self.mode {
+            Mode::Kitty | Mode::Sixel | Mode::ITerm2 => {
+                // kitty draws the frames at their own size, so without the size in pixels they're left alone
+                let params = match self.video_size(size) {
+                    Some((width, height)) => {
+                        format!("width={width},height={height},pixel-aspect-ratio=1/1")
+                    }
+                    None => "pixel-aspect-ratio=1/1".to_owned(),
+                };
+                // png wants the alpha channel to be set
+                let format = if self.mode == Mode::Sixel {
+                    "RGBx"
+                } else {
+                    "RGBA"
+                };
+                (params, format)
+            }
+            _ => {
+                let (width, height) = self.cells_size(size);
+                let size = self.size_caps(width, height);
+                // the pixels are narrower than the cells by as many times as there are more of them down than across
+                let (across, down) = self.cell_pixels();
+                let (aspect_width, aspect_height) = self.cell_aspect;
+                let (aspect_width, aspect_height) = (aspect_width * down, aspect_height * across);
+                (
+                    format!("{size},pixel-aspect-ratio={aspect_width}/{aspect_height}"),
+                    if self.mode == Mode::Luma {
+                        "{I420,NV12}"
+                    } else {
+                        "RGBx"
+                    },
+                )
+            }
+        };
+        format!("video/x-raw,{params},format={format}")
+    }
+
+    /// The size of the terminal's text area in pixels, from `cell_px` if it's set and otherwise from the terminal
+    fn pixel_size(&self, (termwidth, termheight): (u16, u16)) -> Option<(u32, u32)> {
+        match self.cell_px {
+            Some((width, height)) => Some((
+                width as u32 * termwidth as u32,
+                height as u32 * termheight as u32,
+            )),
+            None => term::pixel_size().map(|(width, height)| (width as u32, height as u32)),
+        }
+    }
+
+    /// Whether the frames are squashed, which only the modes with a character per pixel do
+    fn squashes(&self) -> bool {
+        self.squash && matches!(self.mode, Mode::Cells { .. } | Mode::NoColor)
+    }
+
+    /// The width and height fields of the caps. With [`Scale::Fit`] they're ranges, and videoscale picks the biggest
+    /// size in them that keeps the aspect ratio of the video
+    fn size_caps(&self, width: u32, height: u32) -> String {
+        if self.scale == Scale::Fit {
+            format!("width=[1,{width}],height=[1,{height}]")
+        } else {
+            format!("width={width},height={height}")
+        }
+    }
+
+    /// The aspect ratio the video is cropped to with [`Scale::Crop`], which is the shape of the part of the terminal
+    /// the video goes in. `None` when that shape is unknown, like for the image protocols when the size of the
+    /// terminal in pixels isn't
+    fn crop_aspect(&self, (termwidth, termheight): (u16, u16)) -> Option<(u32, u32)> {
+        let rows = self.video_rows(termheight) as u32;
+        match self.mode {
+            Mode::Kitty | Mode::Sixel | Mode::ITerm2 => {
+                // the same size as in `video_caps`
+                let (width, height) = self.pixel_size((termwidth, termheight))?;
+                let rows = rows - (self.mode == Mode::Sixel) as u32;
+                Some((width * termheight as u32, height * rows))
+            }
+            _ => {
+                let (aspect_width, aspect_height) = self.cell_aspect;
+                Some((termwidth as u32 * aspect_width, rows * aspect_height))
+            }
+        }
+        .filter(|_| self.scale == Scale::Crop)
+    }
+
+    /// Moves the video to the middle of the terminal when it's smaller than the terminal from [`Scale::Fit`].
+    /// `width` and `height` are the size of the frames, in a terminal of `term_size`
+    fn centered(
+        &self,
+        options: RenderOptions,
+        (width, height): (u32, u32),
+        term_size: (u16, u16),
+    ) -> RenderOptions {
+        let Some((width, rows)) = self.frame_cells((width, height), term_size) else {
+            return options;
+        };
+        let (columns, video_rows) = options.cells;
+        RenderOptions {
+            row_offset: options.row_offset + video_rows.saturating_sub(rows) / 2,
+            col_offset: columns.saturating_sub(width) / 2,
+            // iterm2 fits the image in these, so they have to be the frame's own or it's drawn from the left again
+            cells: (width.min(columns), rows.min(video_rows)),
+            ..options
+        }
+    }
+
+    /// How many (columns, rows) a frame of `(width, height)` pixels takes up in a terminal of `term_size`, or `None`
+    /// for the image protocols when the size of the terminal in pixels isn't known
+    fn frame_cells(
+        &self,
+        (width, height): (u32, u32),
+        (termwidth, termheight): (u16, u16),
+    ) -> Option<(u32, u32)> {
+        match self.mode {
+            Mode::HalfBlock => Some((width, height.div_ceil(2))),
+            Mode::Quadrant => Some((width.div_ceil(2), height.div_ceil(2))),
+            Mode::Sextant => Some((width.div_ceil(2), height.div_ceil(3))),
+            Mode::Cells { .. } | Mode::NoColor | Mode::Luma => Some((width, height)),
+            Mode::Kitty | Mode::Sixel | Mode::ITerm2 => {
+                // a partly covered cell is still covered
+                let (pixel_width, pixel_height) = self.pixel_size((termwidth, termheight))?;
+                let cell_width = (pixel_width / termwidth as u32).max(1);
+                let cell_height = (pixel_height / termheight as u32).max(1);
+                Some((width.div_ceil(cell_width), height.div_ceil(cell_height)))
+            }
+        }
+    }
+
+    /// Where a frame of `(width, height)` pixels drawn with `options` in a terminal of `term_size` ends up as
+    /// (column, row, columns, rows)
+    fn frame_bounds(
+        &self,
+        options: &RenderOptions,
+        size: (u32, u32),
+        term_size: (u16, u16),
+    ) -> Option<[u32; 4]> {
+        let (columns, rows) = self.frame_cells(size, term_size)?;
+        Some([options.col_offset, options.row_offset, columns, rows])
+    }
+
+    /// The render options with the parts that depend on the terminal size filled in
+    fn render_options(&self, (termwidth, termheight): (u16, u16)) -> RenderOptions {
+        RenderOptions {
+            row_offset: self.top_rows() as u32,
+            cells: (termwidth as u32, self.video_rows(termheight) as u32),
+            ..self.render.clone()
+        }
+    }
+}
+
+/// What a [`Player`] controls on its [`Producer`] besides the frames. Only the counter is required, and the rest do
+/// nothing by default for producers that can't seek, switch videos, or play audio
+pub trait Playback: Producer {
+    /// The frames that were drawn and dropped so far
+    fn counter(&self) -> Arc<FrameCounter>;
+    /// Scales the frames to fit a terminal of `size` with `config`, like after the terminal is resized.
+    /// A new `Initialize` is sent once the frames come in at the new size
+    fn resize(&self, _config: &PlayConfig, _size: (u16, u16)) -> Result<(), Error> {
+        Ok(())
+    }
+    /// The playback speed, which the frames are paced by
+    fn rate(&self) -> f64 {
+        1.
+    }
+    /// Whether the video is paused, which leaves the rate as it was
+    fn is_paused(&self) -> bool {
+        false
+    }
+    /// The volume of the audio from 0 to 1, or `None` when there is no audio to change the volume of
+    fn volume(&self) -> Option<f64> {
+        None
+    }
+    fn set_volume(&self, _volume: f64) -> Result<(), Error> {
+        Ok(())
+    }
+    /// Jumps to `position` in the current video
+    fn seek(&self, _position: Duration) -> Result<(), Error> {
+        Ok(())
+    }
+    /// Skips to the next video in the playlist
+    fn next(&self) -> Result<(), Error> {
+        Ok(())
+    }
+    /// Goes back to the previous video in the playlist
+    fn previous(&self) -> Result<(), Error> {
+        Ok(())
+    }
+    /// The length of the video, if it's known
+    fn duration(&self) -> Option<Duration> {
+        None
+    }
+    /// How far into the video playback is, if it's known
+    fn position(&self) -> Option<Duration> {
+        None
+    }
+    /// How full the network buffer is while playback waits for it, or `None` when it isn't buffering
+    fn buffering(&self) -> Option<u8> {
+        None
+    }
+    /// The text of the embedded subtitle that is on the screen right now, if there is one
+    fn subtitle(&self) -> Option<String> {
+        None
+    }
+}
+
+impl Playback for GstProducer {
+    fn counter(&self) -> Arc<FrameCounter> {
+        GstProducer::counter(self)
+    }
+    fn resize(&self, config: &PlayConfig, size: (u16, u16)) -> Result<(), Error> {
+        if let Some(aspect) = config.crop_aspect(size) {
+            self.set_crop_aspect(CROP_ELEMENT, aspect)?;
+        }
+        GstProducer::resize(self, &config.video_caps(size))
+    }
+    fn rate(&self) -> f64 {
+        GstProducer::rate(self)
+    }
+    fn is_paused(&self) -> bool {
+        GstProducer::is_paused(self)
+    }
+    fn volume(&self) -> Option<f64> {
+        GstProducer::volume(self)
+    }
+    fn set_volume(&self, volume: f64) -> Result<(), Error> {
+        GstProducer::set_volume(self, volume)
+    }
+    fn seek(&self, position: Duration) -> Result<(), Error> {
+        GstProducer::seek(self, position)
+    }
+    fn next(&self) -> Result<(), Error> {
+        GstProducer::next(self)
+    }
+    fn previous(&self) -> Result<(), Error> {
+        GstProducer::previous(self)
+    }
+    fn duration(&self) -> Option<Duration> {
+        GstProducer::duration(self)
+    }
+    fn position(&self) -> Option<Duration> {
+        GstProducer::position(self)
+    }
+    fn buffering(&self) -> Option<u8> {
+        GstProducer::buffering(self)
+    }
+    fn subtitle(&self) -> Option<String> {
+        GstProducer::subtitle(self)
+    }
+}
+
+/// A video that is ready to be drawn, decoded by gstreamer unless the player was made
+/// [`with_producer`](Player::with_producer)
+pub struct Player<P = GstProducer> {
+    config: PlayConfig,
+    producer: P,
+    // set by whoever owns the signal handlers, or by our own handlers when it's `None`
+    interrupt: Option<Arc<AtomicBool>>,
+    profile: Option<Profile>,
+    frames_drawn: usize,
+}
+
+impl Player {
+    /// Initializes gstreamer and starts decoding, so the errors about the video come up before anything is drawn
+    pub fn new(config: PlayConfig) -> Result<Self, Error> {
+        gst::init()?;
+        let convert = if config.no_convert {
+            ""
+        } else {
+            "videoconvert !"
+        };
+        let scale_method = config
+            .scale_method
+            .map_or_else(String::new, |method| format!(" method={}", method.nick()));
+        // aspectratiocrop cuts off the same amount on both sides, with the odd pixel left over on one of them
+        let crop =
+            config
+                .crop_aspect(config.term_size)
+                .map_or_else(String::new, |(numer, denom)| {
+                    format!("aspectratiocrop name={CROP_ELEMENT} aspect-ratio={numer}/{denom} ! ")
+                });
+        let producer = GstProducer::new(
+            &config.source,
+            &format!(
+                "{crop}{convert} videoscale{scale_method} ! appsink name=app_sink caps={caps}",
+                caps = config.video_caps(config.term_size),
+            ),
+            &config.producer,
+        )?;
+        Ok(Self::with_producer(config, producer))
+    }
+
+    /// What the decoder found in the video
+    pub fn probe(&self) -> StreamInfo {
+        self.producer.probe()
+    }
+}
+
+impl<P: Playback> Player<P> {
+    /// Plays the frames from `producer` instead of decoding [`PlayConfig::source`]. The frames have to be in the
+    /// format the renderer for [`PlayConfig::mode`] expects, which is RGBx for all but `Mode::Luma` and the image
+    /// protocols
+    pub fn with_producer(config: PlayConfig, producer: P) -> Self {
+        Self {
+            config,
+            producer,
+            interrupt: None,
+            profile: None,
+            frames_drawn: 0,
+        }
+    }
+
+    /// Stops the video once `flag` is set, instead of installing signal handlers for ctrl+c and friends. This lets the
+    /// handlers live on another thread than the one the video is drawn on, which only needs to check the flag
+    pub fn set_interrupt(&mut self, flag: Arc<AtomicBool>) {
+        self.interrupt = Some(flag);
+    }
+
+    /// The frames that were drawn and dropped so far
+    pub fn counter(&self) -> Arc<FrameCounter> {
+        self.producer.counter()
+    }
+
+    /// Where the time went in the last `run`, if [`PlayConfig::profile`] is on
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+    /// How many frames the last `run` drew
+    pub fn frames_drawn(&self) -> usize {
+        self.frames_drawn
+    }
+
+    /// Draws the video into `out` until it ends, or until the user quits when the player is interactive
+    pub fn run(&mut self, out: impl Write) -> Result<(), Error> {
+        self.run_with_resize(out, |_| ())
+    }
+
+    /// Like [`run`](Self::run), but calls `on_resize` with the new terminal size whenever the video follows a resize
+    pub fn run_with_resize(
+        &mut self,
+        mut out: impl Write,
+        mut on_resize: impl FnMut((u16, u16)),
+    ) -> Result<(), Error> {
+        write!(out, "\x1b[2J")?; // clear the screen
+        let interrupt = match &self.interrupt {
+            Some(interrupt) => interrupt.clone(),
+            None => {
+                let interrupt = if self.config.interactive {
+                    term::interrupt_flag()?
+                } else {
+                    Arc::new(AtomicBool::new(false))
+                };
+                self.interrupt = Some(interrupt.clone());
+                interrupt
+            }
+        };
+        let wait = &self.producer.subscribe();
+        let factory = self.config.mode.renderer();
+        (self.frames_drawn, self.profile) = do_run(
+            factory,
+            wait,
+            &self.producer,
+            &self.config,
+            &interrupt,
+            &mut out,
+            &mut on_resize,
+        )?;
+        Ok(())
+    }
+}
+
+impl Mode {
+    /// Whether the mode draws with 24-bit colors, which not every terminal understands
+    pub fn needs_truecolor(self) -> bool {
+        matches!(
+            self,
+            Self::HalfBlock
+                | Self::Quadrant
+                | Self::Sextant
+                | Self::Cells {
+                    colors: Colors::Truecolor,
+                    ..
+                }
+        )
+    }
+    /// Picks the renderer for this mode, detecting the colors from the environment if they're `Colors::Auto`
+    pub fn renderer(self) -> RendererFactory {
+        let (colors, grayscale, background) = match self {
+            Self::Sixel => return boxed::<SixelRenderer>,
+            Self::ITerm2 => return boxed::<ITerm2Renderer>,
+            Self::HalfBlock => return boxed::<HalfBlockRenderer>,
+            Self::Quadrant => return boxed::<QuadrantRenderer>,
+            Self::Sextant => return boxed::<SextantRenderer>,
+            Self::Kitty => return boxed::<KittyRenderer>,
+            Self::NoColor => return boxed::<DefaultRenderer<NoColor>>,
+            Self::Luma => return boxed::<LumaRenderer>,
+            Self::Cells {
+                colors,
+                grayscale,
+                background,
+            } => (colors, grayscale, background),
+        };
+        match colors {
+            Colors::Auto => Self::Cells {
+                colors: detect_colors(
+                    std::env::var("COLORTERM").ok().as_deref(),
+                    std::env::var("TERM").ok().as_deref(),
+                ),
+                grayscale,
+                background,
+            }
+            .renderer(),
+            Colors::Ansi16 => cells::<Ansi16, BackgroundAnsi16>(grayscale, background),
+            Colors::Ansi256 => cells::<Ansi256, BackgroundAnsi256>(grayscale, background),
+            Colors::Truecolor => cells::<Rgb, BackgroundRgb>(grayscale, background),
+        }
+    }
+}
+
+/// The renderer for colored cells, which draws characters in `F` or spaces on `B` with `background`
+fn cells<F: Colorize + 'static, B: Colorize + 'static>(
+    grayscale: bool,
+    background: bool,
+) -> RendererFactory
+where
+    DefaultRenderer<F>: Renderer,
+    DefaultRenderer<Grayscale<F>>: Renderer,
+    DefaultRenderer<B>: Renderer,
+    DefaultRenderer<Grayscale<B>>: Renderer,
+{
+    if background {
+        maybe_grayscale::<B>(grayscale)
+    } else {
+        maybe_grayscale::<F>(grayscale)
+    }
+}
+
+/// The renderer for cells in `C`, reduced to its luminance with `grayscale`
+fn maybe_grayscale<C: Colorize + 'static>(grayscale: bool) -> RendererFactory
+where
+    DefaultRenderer<C>: Renderer,
+    DefaultRenderer<Grayscale<C>>: Renderer,
+{
+    if grayscale {
+        boxed::<DefaultRenderer<Grayscale<C>>>
+    } else {
+        boxed::<DefaultRenderer<C>>
+    }
+}
+
+/// Plays a video into `out` from start to finish
+pub fn play(config: PlayConfig, out: impl Write) -> Result<(), Error> {
+    Player::new(config)?.run(out)
+}
+
+/// How often the render loop checks for the end of the video or an interrupt while waiting for a frame
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How late a frame can be with `pace` before it's skipped
+const LATE_FRAME: Duration = Duration::from_millis(100);
+
+/// How late the frames can fall behind the clock with `pace` before it starts over from the current frame, like when
+/// the decoder stalled
+const RESYNC: Duration = Duration::from_secs(1);
+
+/// Lines the frames up with the clock by their timestamps
+#[derive(Default)]
+struct Pacer {
+    // when a frame was shown and its timestamp
+    anchor: Option<(Instant, Duration)>,
+}
+
+/// What to do with a frame, from [`Pacer::wait`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pace {
+    Show,
+    /// The frame is too late to show
+    Skip,
+    /// The frame isn't due yet, so ask again after the keys and ctrl+c get their turn
+    NotYet,
+}
+
+impl Pacer {
+    /// Waits until the frame at `pts` is due at the playback `rate`, but for no longer than `POLL_INTERVAL` at a time.
+    ///
+    /// A frame far in the future is still waited for, since a gif can hold a frame for as long as it likes, so the
+    /// render loop has to keep asking until it's due
+    fn wait(&mut self, pts: Duration, rate: f64) -> Pace {
+        let now = Instant::now();
+        // an earlier timestamp than the anchor means the video started over
+        let due = self.anchor.and_then(|(shown, anchor_pts)| {
+            let offset = pts.checked_sub(anchor_pts)?.div_f64(rate.max(f64::EPSILON));
+            Some(shown + offset)
+        });
+        match due {
+            Some(due) if due > now => {
+                let left = due - now;
+                std::thread::sleep(left.min(POLL_INTERVAL));
+                if left <= POLL_INTERVAL {
+                    Pace::Show
+                } else {
+                    Pace::NotYet
+                }
+            }
+            Some(due) if now - due < RESYNC => {
+                if now - due < LATE_FRAME {
+                    Pace::Show
+                } else {
+                    Pace::Skip
+                }
+            }
+            _ => {
+                self.anchor = Some((now, pts));
+                Pace::Show
+            }
+        }
+    }
+    /// Starts over from the next frame, for when the timestamps jump like after a seek
+    fn reset(&mut self) {
+        self.anchor = None;
+    }
+}
+
+/// How long the frames took to draw, added up over the whole video
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    pub frames: u32,
+    /// Turning the frames into cells or pixels, with the color adjustments
+    pub consume: Duration,
+    /// Diffing the frames against the last ones and writing the escapes and overlays into the output buffer
+    pub render: Duration,
+    /// Flushing the buffer out, which is where a slow terminal shows up. With [`PlayConfig::flush_interval`] only some
+    /// frames are flushed, so this is spread over the ones that weren't
+    pub flush: Duration,
+}
+
+impl Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let per_frame = |total: Duration| total.as_secs_f64() * 1000. / self.frames.max(1) as f64;
+        write!(
+            f,
+            "each frame took {:.2}ms to consume, {:.2}ms to render, and {:.2}ms to write out",
+            per_frame(self.consume),
+            per_frame(self.render),
+            per_frame(self.flush)
+        )
+    }
+}
+
+/// The time since `timer` started, which is started over, or zero when it isn't running
+fn lap(timer: &mut Option<Instant>) -> Duration {
+    let Some(start) = timer else {
+        return Duration::ZERO;
+    };
+    let now = Instant::now();
+    let elapsed = now - *start;
+    *start = now;
+    elapsed
+}
+
+/// How many lines of subtitles fit under the video
+const SUBTITLE_ROWS: u16 = 2;
+
+/// How much the up and down arrows change the volume
+const VOLUME_STEP: f64 = 0.05;
+
+/// Averages each pair of rows of an RGBx `frame` that is `width` pixels wide into one row of `out`.
+/// An odd last row is kept as it is
+fn squash_rows(frame: &[u8], width: usize, out: &mut Vec<u8>) {
+    out.clear();
+    for rows in frame.chunks(width * 8) {
+        let (top, bottom) = rows.split_at((width * 4).min(rows.len()));
+        let bottom = if bottom.is_empty() { top } else { bottom };
+        out.extend(
+            top.iter()
+                .zip(bottom)
+                .map(|(top, bottom)| (*top as u16 + *bottom as u16).div_ceil(2) as u8),
+        );
+    }
+}
+
+/// Saves an RGBx `frame` to a PNG at `path`. The fourth byte is padding or the character for ascii mode, so it's left
+/// out rather than being saved as alpha
+fn dump_frame(path: &Path, (width, height): (u32, u32), frame: &[u8]) -> Result<(), Error> {
+    let rgb: Vec<u8> = frame
+        .chunks_exact(4)
+        .flat_map(|pixel| [pixel[0], pixel[1], pixel[2]])
+        .collect();
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&rgb)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Saves the frame in `slot` for [`PlayConfig::dump_frame`] once `frames_seen` reaches its number, putting it back
+/// in the slot afterwards so it can still be drawn. A frame that doesn't match `decoded_size` was decoded before a
+/// resize, so the next one that does is saved instead
+fn dump_if_due(
+    config: &PlayConfig,
+    producer: &impl Producer,
+    slot: usize,
+    frames_seen: u64,
+    decoded_size: (u32, u32),
+    dumped: &mut bool,
+    frame: &mut Vec<u8>,
+) -> Result<(), Error> {
+    let Some((_, path)) = config
+        .dump_frame
+        .as_ref()
+        .filter(|(n, _)| !*dumped && frames_seen >= *n)
+    else {
+        return Ok(());
+    };
+    if !producer.swap_frame(slot, frame) {
+        return Ok(());
+    }
+    let (width, height) = decoded_size;
+    let result = if frame.len() == width as usize * height as usize * 4 {
+        *dumped = true;
+        dump_frame(path, decoded_size, frame)
+    } else {
+        Ok(())
+    };
+    producer.swap_frame(slot, frame);
+    result
+}
+
+/// The name of the element that crops the video with [`Scale::Crop`], so it can follow the terminal when it's resized
+const CROP_ELEMENT: &str = "crop";
+
+/// Clears the screen around `bounds`, filling it with the padding color with [`Scale::Fit`].
+///
+/// The cells inside are left alone since a new renderer draws all of them with its first frame anyway, and clearing
+/// them too would make the screen flash. Without bounds the whole screen is cleared
+fn clear_screen(
+    config: &PlayConfig,
+    bounds: Option<[u32; 4]>,
+    (columns, rows): (u16, u16),
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    // clearing fills the cells with the current background color, which is still the last one drawn without a pad
+    // color
+    let pad_color = config.pad_color.filter(|_| config.scale == Scale::Fit);
+    match pad_color {
+        Some([r, g, b]) => write!(out, "\x1b[48;2;{r};{g};{b}m")?,
+        None => out.write_all(b"\x1b[0m")?,
+    }
+    match bounds {
+        Some([left, top, width, height]) => {
+            for row in 0..rows as u32 {
+                if !(top..top + height).contains(&row) {
+                    write!(out, "\x1b[{};1H\x1b[2K", row + 1)?;
+                    continue;
+                }
+                // the column right before the frame, and everything to the left of it
+                if left > 0 {
+                    write!(out, "\x1b[{};{left}H\x1b[1K", row + 1)?;
+                }
+                if left + width < columns as u32 {
+                    write!(out, "\x1b[{};{}H\x1b[K", row + 1, left + width + 1)?;
+                }
+            }
+        }
+        None => out.write_all(b"\x1b[2J")?,
+    }
+    if pad_color.is_some() {
+        out.write_all(b"\x1b[0m")?;
+    }
+    Ok(())
+}
+
+fn do_run(
+    factory: RendererFactory,
+    wait: &Receiver<ProducerMessage>,
+    producer: &impl Playback,
+    config: &PlayConfig,
+    interrupt: &AtomicBool,
+    mut out: &mut dyn Write,
+    on_resize: &mut dyn FnMut((u16, u16)),
+) -> Result<(usize, Option<Profile>), Error> {
+    let keys = if config.interactive {
+        Keys::start()
+    } else {
+        None
+    };
+    let mut renderer = None;
+    let mut stats = config.stats.then(StatsOverlay::new);
+    let mut progress = config.progress.then(ProgressBar::new);
+    let mut volume = VolumeIndicator::new();
+    let counter = producer.counter();
+    let mut watcher = config
+        .follow_resize
+        .then(|| resize_watcher::default_watcher(config.term_size));
+    let mut stalled = Duration::ZERO;
+    let mut term_size = config.term_size;
+    let mut options = config.render_options(term_size);
+    // where the frames of the current renderer go, so only the rest of the screen has to be cleared
+    let mut bounds = None;
+    let mut needs_clear = false;
+    let frame_interval = config
+        .max_fps
+        .map(|fps| Duration::from_secs_f64(1. / fps as f64));
+    let mut last_render: Option<Instant> = None;
+    let mut pacer = config.pace.then(Pacer::default);
+    let mut profile = config.profile.then(Profile::default);
+    let mut frames_drawn = 0;
+    // when the output held back by `flush_interval` has to go out
+    let mut flush_due: Option<Instant> = None;
+    // a message that was read while looking for newer frames with `batch`
+    let mut pending = None;
+    // a frame that is waiting for its timestamp with `pace`, which was already counted and let through the fps cap
+    let mut held = None;
+    // every frame that came in, including the ones that were skipped, for `dump_frame`
+    let mut frames_seen = 0;
+    let mut dumped = false;
+    // swapped with the producer's slots, so the frames are read without holding any locks
+    let mut frame = Vec::new();
+    // the size of the frames before they're squashed, and where they go after
+    let mut decoded_size = (0, 0);
+    let mut squashed = Vec::new();
+    'render: loop {
+        if interrupt.load(Ordering::Relaxed) {
+            break;
+        }
+        if let Some(size) = watcher.as_mut().and_then(|watcher| watcher.resized()) {
+            // the renderer gets recreated once frames come in at the new size
+            term_size = size;
+            options = config.render_options(size);
+            producer.resize(config, size)?;
+            on_resize(size);
+        }
+        while let Some(input) = keys.as_ref().and_then(Keys::poll) {
+            match input {
+                Input::Key(b'n') => {
+                    producer.next()?;
+                    pacer.as_mut().map(Pacer::reset);
+                }
+                Input::Key(b'p') => {
+                    producer.previous()?;
+                    pacer.as_mut().map(Pacer::reset);
+                }
+                Input::Key(b'q') => break 'render,
+                Input::Up | Input::Down => {
+                    if let Some(current) = producer.volume() {
+                        let step = if input == Input::Up {
+                            VOLUME_STEP
+                        } else {
+                            -VOLUME_STEP
+                        };
+                        producer.set_volume(current + step)?;
+                        volume.show(producer.volume().unwrap_or(current));
+                    }
+                }
+                Input::Click(column, row) => {
+                    if let Some(time) = progress.as_ref().and_then(|p| p.time_at((column, row))) {
+                        producer.seek(time)?;
+                        pacer.as_mut().map(Pacer::reset);
+                    }
+                }
+                _ => (),
+            }
+        }
+        let resumed = held.is_some();
+        let msg = match held.take().or_else(|| pending.take()) {
+            Some(msg) => msg,
+            None => match wait.recv_timeout(flush_due.map_or(POLL_INTERVAL, |due| {
+                due.saturating_duration_since(Instant::now())
+                    .min(POLL_INTERVAL)
+            })) {
+                Ok(msg) => {
+                    stalled = Duration::ZERO;
+                    msg
+                }
+                // nothing came in to carry the held back output along with it, so it goes out on its own
+                Err(RecvTimeoutError::Timeout) if flush_due.is_some() => {
+                    out.flush()?;
+                    flush_due = None;
+                    continue;
+                }
+                // waiting on the network isn't a stall, so show how far along it is instead
+                Err(RecvTimeoutError::Timeout) if producer.buffering().is_some() => {
+                    if let Some(stats) = &stats {
+                        stats.write(&counter, producer.buffering(), term_size, &mut out)?;
+                        out.flush()?;
+                    }
+                    continue;
+                }
+                // keep waiting for a stalled producer for a bit, but stop right away once the video is over in case the
+                // end came without an `Eos`
+                Err(RecvTimeoutError::Timeout)
+                    if !producer.is_finished()
+                        && config.stall_timeout.is_none_or(|timeout| stalled < timeout) =>
+                {
+                    stalled += POLL_INTERVAL;
+                    continue;
+                }
+                Err(_) => break,
+            },
+        };
+        match msg {
+            ProducerMessage::Initialize { width, height } => {
+                // the last frame at the old size would otherwise linger around the new one. this waits for the
+                // first frame at the new size so the old one stays up until then
+                needs_clear |= renderer.is_some() || config.pad_color.is_some();
+                decoded_size = (width, height);
+                // the renderer only sees the frames after they're squashed
+                let height = if config.squashes() {
+                    height.div_ceil(2)
+                } else {
+                    height
+                };
+                // centered with `Scale::Fit`
+                let frame_options = if config.scale == Scale::Fit {
+                    config.centered(options.clone(), (width, height), term_size)
+                } else {
+                    options.clone()
+                };
+                bounds = config.frame_bounds(&frame_options, (width, height), term_size);
+                renderer = Some(factory(width, height, &frame_options));
+            }
+            ProducerMessage::FrameReady { mut slot, mut pts } => {
+                if !resumed {
+                    frames_seen += 1;
+                    dump_if_due(
+                        config,
+                        producer,
+                        slot,
+                        frames_seen,
+                        decoded_size,
+                        &mut dumped,
+                        &mut frame,
+                    )?;
+                    if config.batch {
+                        // the frame is held until the fps cap allows it, and whatever comes in meanwhile replaces it
+                        if let (Some(interval), Some(last)) = (frame_interval, last_render) {
+                            if let Some(wait) = interval.checked_sub(last.elapsed()) {
+                                std::thread::sleep(wait);
+                            }
+                        }
+                        loop {
+                            match wait.try_recv() {
+                                Ok(ProducerMessage::FrameReady {
+                                    slot: newer,
+                                    pts: newer_pts,
+                                }) => {
+                                    counter.merged.fetch_add(1, Ordering::SeqCst);
+                                    (slot, pts) = (newer, newer_pts);
+                                    frames_seen += 1;
+                                    dump_if_due(
+                                        config,
+                                        producer,
+                                        slot,
+                                        frames_seen,
+                                        decoded_size,
+                                        &mut dumped,
+                                        &mut frame,
+                                    )?;
+                                }
+                                // anything else waits for the next time around
+                                Ok(msg) => {
+                                    pending = Some(msg);
+                                    break;
+                                }
+                                Err(_) => break,
+                            }
+                        }
+                    } else if let (Some(interval), Some(last)) = (frame_interval, last_render) {
+                        if last.elapsed() < interval {
+                            counter.throttled.fetch_add(1, Ordering::SeqCst);
+                            continue;
+                        }
+                    }
+                }
+                if producer.is_paused() {
+                    // the frame that comes up while paused is shown as it is, and the clock starts over from the
+                    // first one after it
+                    pacer.as_mut().map(Pacer::reset);
+                } else if let (Some(pacer), Some(pts)) = (&mut pacer, pts) {
+                    match pacer.wait(pts, producer.rate()) {
+                        Pace::Show => (),
+                        Pace::Skip => {
+                            counter.late.fetch_add(1, Ordering::SeqCst);
+                            continue;
+                        }
+                        Pace::NotYet => {
+                            held = Some(ProducerMessage::FrameReady {
+                                slot,
+                                pts: Some(pts),
+                            });
+                            continue;
+                        }
+                    }
+                }
+                last_render = Some(Instant::now());
+                let r = renderer.as_mut().expect("renderer should be initialized");
+                if volume.expired() {
+                    // only the cells that change get drawn, so draw all of them to get the video under the volume back
+                    needs_clear = true;
+                    r.invalidate();
+                }
+                assert!(
+                    producer.swap_frame(slot, &mut frame),
+                    "frame should be ready"
+                );
+                let mut timer = profile.is_some().then(Instant::now);
+                let consumed = if config.squashes() {
+                    squash_rows(&frame, decoded_size.0 as usize, &mut squashed);
+                    r.consume_frame(&squashed)
+                } else {
+                    r.consume_frame(&frame)
+                };
+                // each slot keeps the frame it was given, so one decoded at the old size can still come in after the
+                // renderer for the new size was made. skip those until the frames at the new size catch up
+                if consumed.is_err() {
+                    continue;
+                }
+                let consume_time = lap(&mut timer);
+                // wrapped here rather than in each renderer so every mode gets it
+                if config.sync {
+                    out.write_all(BEGIN_SYNC)?;
+                }
+                let cleared = std::mem::take(&mut needs_clear);
+                if cleared {
+                    clear_screen(config, bounds, term_size, out)?;
+                }
+                r.render_frame(out)?;
+                // written after the frame so the next frame's cursor jumps don't clobber it
+                if let Some(stats) = &mut stats {
+                    stats.record_frame();
+                    stats.write(&counter, producer.buffering(), term_size, &mut out)?;
+                }
+                // the bar is drawn less often than the frames, so it has to come back right away after a clear
+                if let Some(progress) = progress.as_mut().filter(|p| cleared || p.due()) {
+                    let (position, duration) = (producer.position(), producer.duration());
+                    progress.write(position, duration, term_size, &mut out)?;
+                }
+                if let Some(subtitles) = &config.subtitles {
+                    let first_row = config.top_rows() + config.video_rows(term_size.1) + 1;
+                    let subtitle = match subtitles {
+                        Subtitles::Embedded => producer.subtitle(),
+                        Subtitles::Cues(cues) => producer
+                            .position()
+                            .and_then(|position| subtitle::active_text(cues, position)),
+                    };
+                    overlay::write_subtitle(
+                        subtitle.as_deref(),
+                        (first_row, SUBTITLE_ROWS),
+                        term_size.0,
+                        &mut out,
+                    )?;
+                }
+                // redrawn with every frame like the stats, so it comes back right away after a clear
+                if let Some(banner) = &config.banner {
+                    let row = match banner.position {
+                        BannerPosition::Top => config.stats as u16 + 1,
+                        BannerPosition::Bottom => {
+                            config.top_rows()
+                                + config.video_rows(term_size.1)
+                                + config.subtitle_rows()
+                                + 1
+                        }
+                    };
+                    overlay::write_banner(&banner.text, row, term_size.0, &mut out)?;
+                }
+                volume.write(term_size, &mut out)?;
+                if config.sync {
+                    out.write_all(END_SYNC)?;
+                }
+                let render_time = lap(&mut timer);
+                frames_drawn += 1;
+                let flush = match config.flush_interval {
+                    Some(interval) => {
+                        let due = *flush_due.get_or_insert_with(|| Instant::now() + interval);
+                        Instant::now() >= due
+                    }
+                    None => true,
+                };
+                if flush {
+                    out.flush()?;
+                    flush_due = None;
+                }
+                if let Some(profile) = &mut profile {
+                    profile.frames += 1;
+                    profile.consume += consume_time;
+                    profile.render += render_time;
+                    // the frames in between only wrote into the buffer, so there is nothing to time for them
+                    if flush {
+                        profile.flush += lap(&mut timer);
+                    }
+                }
+                if config.single_frame {
+                    break;
+                }
+            }
+            ProducerMessage::Error { message } => return Err(message.into()),
+            ProducerMessage::Eos => break,
+        }
+    }
+    if flush_due.is_some() {
+        out.flush()?;
+    }
+    Ok((frames_drawn, profile))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::producer::{Decoder, Repeat};
+
+    fn config(mode: Mode) -> PlayConfig {
+        PlayConfig {
+            source: Source::Live(String::new()),
+            producer: ProducerOptions {
+                timeout: Duration::from_secs(1),
+                decoder: Decoder::Auto,
+                repeat: Repeat::Never,
+                verbose: false,
+                start_at: None,
+                rate: 1.,
+                buffer: 2,
+                buffer_duration: None,
+                rtsp_latency: None,
+                rtsp_tcp: false,
+                subtitles: None,
+                max_bad_samples: 0,
+            },
+            render: RenderOptions::default(),
+            mode,
+            no_convert: false,
+            sync: false,
+            stats: false,
+            progress: false,
+            subtitles: None,
+            banner: None,
+            scale: Scale::Fill,
+            pad_color: None,
+            scale_method: None,
+            squash: false,
+            stall_timeout: None,
+            max_fps: None,
+            pace: false,
+            batch: false,
+            term_size: (80, 24),
+            cell_aspect: (1, 2),
+            cell_px: None,
+            interactive: false,
+            follow_resize: false,
+            single_frame: false,
+            flush_interval: None,
+            profile: false,
+            dump_frame: None,
+        }
+    }
+
+    const CELLS: Mode = Mode::Cells {
+        colors: Colors::Truecolor,
+        grayscale: false,
+        background: false,
+    };
+
+    #[test]
+    fn video_caps_for_the_character_modes() {
+        let caps = |config: &PlayConfig| config.video_caps((80, 24));
+        assert_eq!(
+            caps(&config(CELLS)),
+            "video/x-raw,width=80,height=24,pixel-aspect-ratio=1/2,format=RGBx"
+        );
+        assert_eq!(
+            caps(&PlayConfig {
+                squash: true,
+                ..config(CELLS)
+            }),
+            "video/x-raw,width=80,height=48,pixel-aspect-ratio=2/2,format=RGBx"
+        );
+        assert_eq!(
+            caps(&config(Mode::HalfBlock)),
+            "video/x-raw,width=80,height=48,pixel-aspect-ratio=2/2,format=RGBx"
+        );
+        assert_eq!(
+            caps(&config(Mode::Quadrant)),
+            "video/x-raw,width=160,height=48,pixel-aspect-ratio=2/4,format=RGBx"
+        );
+        assert_eq!(
+            caps(&config(Mode::Sextant)),
+            "video/x-raw,width=160,height=72,pixel-aspect-ratio=3/4,format=RGBx"
+        );
+        assert_eq!(
+            caps(&config(Mode::Luma)),
+            "video/x-raw,width=80,height=24,pixel-aspect-ratio=1/2,format={I420,NV12}"
+        );
+        // the overlays take rows away from the video, and fit lets videoscale pick a smaller size
+        assert_eq!(
+            caps(&PlayConfig {
+                stats: true,
+                progress: true,
+                scale: Scale::Fit,
+                ..config(CELLS)
+            }),
+            "video/x-raw,width=[1,80],height=[1,22],pixel-aspect-ratio=1/2,format=RGBx"
+        );
+    }
+
+    #[test]
+    fn video_caps_for_the_image_protocols() {
+        let config = PlayConfig {
+            cell_px: Some((10, 20)),
+            ..config(Mode::Sixel)
+        };
+        // the last row is left off for sixel
+        assert_eq!(config.video_size((80, 24)), Some((800, 460)));
+        assert_eq!(
+            config.video_caps((80, 24)),
+            "video/x-raw,width=800,height=460,pixel-aspect-ratio=1/1,format=RGBx"
+        );
+    }
+
+    #[test]
+    fn fit_centers_the_image_protocols() {
+        let config = PlayConfig {
+            cell_px: Some((10, 20)),
+            scale: Scale::Fit,
+            ..config(Mode::Kitty)
+        };
+        // a 4:3 video fit into the 800x480 pixels of an 80x24 terminal, which covers 64x24 cells
+        let options = config.centered(config.render_options((80, 24)), (640, 480), (80, 24));
+        assert_eq!((options.col_offset, options.row_offset), (8, 0));
+        assert_eq!(options.cells, (64, 24));
+        assert_eq!(
+            config.frame_bounds(&options, (640, 480), (80, 24)),
+            Some([8, 0, 64, 24])
+        );
+        // without the size in pixels the image is left where it is
+        let config = PlayConfig {
+            cell_px: None,
+            ..config
+        };
+        assert_eq!(config.frame_cells((640, 480), (80, 24)), None);
+    }
+
+    #[test]
+    fn detect_colors_from_the_environment() {
+        let cases = [
+            (None, None, Colors::Ansi16),
+            (None, Some("xterm"), Colors::Ansi16),
+            (None, Some("xterm-256color"), Colors::Ansi256),
+            (Some("truecolor"), None, Colors::Truecolor),
+            (Some("24bit"), Some("xterm"), Colors::Truecolor),
+            (
+                Some("truecolor"),
+                Some("screen-256color"),
+                Colors::Truecolor,
+            ),
+            // only the values that mean truecolor count
+            (Some("yes"), Some("xterm-256color"), Colors::Ansi256),
+            (Some(""), Some("dumb"), Colors::Ansi16),
+            (None, Some("xterm-direct"), Colors::Truecolor),
+            (None, Some("iterm2-truecolor"), Colors::Truecolor),
+            (None, Some("xterm-24bit"), Colors::Truecolor),
+            // terminals that always support it, even when $COLORTERM didn't make it over ssh
+            (None, Some("xterm-kitty"), Colors::Truecolor),
+            (None, Some("alacritty"), Colors::Truecolor),
+            (None, Some("foot"), Colors::Truecolor),
+            (None, Some("wezterm"), Colors::Truecolor),
+            (None, Some("xterm-ghostty"), Colors::Truecolor),
+        ];
+        for (colorterm, term, colors) in cases {
+            assert_eq!(
+                detect_colors(colorterm, term),
+                colors,
+                "{colorterm:?} {term:?}"
+            );
+        }
+    }
+}
@@ -3,31 +3,107 @@ use gstreamer as gst;
 use std::{
     fmt::Display,
     sync::{
-        atomic::AtomicUsize,
+        atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering},
         mpsc::{sync_channel, Receiver, SyncSender},
-        Arc, Mutex, MutexGuard,
+        Arc, Mutex,
     },
     time::Duration,
 };
 
 use gst_app::AppSink;
 use gstreamer_app as gst_app;
+
+use crate::subtitle::{strip_tags, Cue};
 pub type Error = Box<dyn std::error::Error>;
 
 #[derive(Debug, Clone)]
 pub enum ProducerMessage {
-    Initialize { width: u32, height: u32 },
-    FrameReady,
+    Initialize {
+        width: u32,
+        height: u32,
+    },
+    /// A new frame is in `slot`, which stays untouched until the channel has gone around the buffer once
+    FrameReady {
+        slot: usize,
+        /// When the frame should be shown in the time of the video, if the decoder knows
+        pts: Option<Duration>,
+    },
+    /// The pipeline failed and won't produce any more frames
+    Error {
+        message: String,
+    },
+    /// The video is over and won't produce any more frames. It comes after every frame that was sent, so it's safe
+    /// to stop as soon as it's received
+    Eos,
 }
 pub trait Producer {
     fn subscribe(&mut self) -> Receiver<ProducerMessage>;
-    fn frame(&self) -> Option<MutexGuard<Vec<u8>>>;
+    /// Swaps the frame from the `FrameReady` message for `slot` into `frame`, returning false if there's no such slot.
+    /// The old contents of `frame` go into the slot to be written over by a later frame, so the slot is only locked
+    /// long enough to swap the two and the producer never waits for the renderer
+    fn swap_frame(&self, slot: usize, frame: &mut Vec<u8>) -> bool;
+    /// Whether the producer reached the end of the video and won't produce any more frames,
+    /// as opposed to just taking a while to produce the next one
+    fn is_finished(&self) -> bool;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct FrameCounter {
     pub dropped: AtomicUsize,
     pub not_dropped: AtomicUsize,
+    /// Frames the render loop skipped on purpose to stay under the fps cap. These are also counted in `not_dropped`
+    pub throttled: AtomicUsize,
+    /// Frames the render loop skipped because they came in too late to show with `--pace`.
+    /// These are also counted in `not_dropped`
+    pub late: AtomicUsize,
+    /// Samples that couldn't be read and were skipped, like the odd broken one from a flaky stream.
+    /// These aren't counted anywhere else
+    pub bad: AtomicUsize,
+    /// Frames the render loop skipped for a newer one with `--batch`, whose changes went out with it.
+    /// These are also counted in `not_dropped`
+    pub merged: AtomicUsize,
+}
+
+impl FrameCounter {
+    pub fn dropped_percentage(&self) -> f32 {
+        let dropped = self.dropped.load(std::sync::atomic::Ordering::SeqCst);
+        let not_dropped = self.not_dropped.load(std::sync::atomic::Ordering::SeqCst);
+        dropped as f32 / (dropped + not_dropped) as f32 * 100.
+    }
+    /// The counts as a JSON object, for scripts that would otherwise have to scrape the `Display` summary
+    pub fn to_json(&self) -> String {
+        let [dropped, not_dropped, throttled, late, bad, merged] = self.counts();
+        format!(
+            r#"{{"dropped": {dropped}, "not_dropped": {not_dropped}, "throttled": {throttled}, "late": {late}, "bad": {bad}, "merged": {merged}}}"#
+        )
+    }
+    fn counts(&self) -> [usize; 6] {
+        [
+            &self.dropped,
+            &self.not_dropped,
+            &self.throttled,
+            &self.late,
+            &self.bad,
+            &self.merged,
+        ]
+        .map(|count| count.load(std::sync::atomic::Ordering::SeqCst))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for FrameCounter {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let [dropped, not_dropped, throttled, late, bad, merged] = self.counts();
+        let mut state = serializer.serialize_struct("FrameCounter", 6)?;
+        state.serialize_field("dropped", &dropped)?;
+        state.serialize_field("not_dropped", &not_dropped)?;
+        state.serialize_field("throttled", &throttled)?;
+        state.serialize_field("late", &late)?;
+        state.serialize_field("bad", &bad)?;
+        state.serialize_field("merged", &merged)?;
+        state.end()
+    }
 }
 
 impl Display for FrameCounter {
@@ -38,93 +114,485 @@ impl Display for FrameCounter {
         write!(
             f,
             "dropped {dropped} frames of {total} ({:.2}%)",
-            dropped as f32 / total as f32 * 100.
-        )
+            self.dropped_percentage()
+        )?;
+        let throttled = self.throttled.load(std::sync::atomic::Ordering::SeqCst);
+        if throttled > 0 {
+            write!(f, ", and skipped {throttled} for the fps cap")?;
+        }
+        let late = self.late.load(std::sync::atomic::Ordering::SeqCst);
+        if late > 0 {
+            write!(f, ", and skipped {late} that were late")?;
+        }
+        let bad = self.bad.load(std::sync::atomic::Ordering::SeqCst);
+        if bad > 0 {
+            write!(f, ", and skipped {bad} samples that couldn't be read")?;
+        }
+        let merged = self.merged.load(std::sync::atomic::Ordering::SeqCst);
+        if merged > 0 {
+            write!(f, ", and merged {merged} into newer ones")?;
+        }
+        Ok(())
+    }
+}
+/// An audio or subtitle track in the video
+#[derive(Debug, Clone, Default)]
+pub struct Track {
+    /// Usually an ISO 639 code like `eng`
+    pub language: Option<String>,
+    pub codec: Option<String>,
+}
+
+/// What the video looks like before it's scaled to the terminal, from [`GstProducer::probe`]
+#[derive(Debug, Clone, Default)]
+pub struct StreamInfo {
+    pub size: Option<(i32, i32)>,
+    /// As the numerator and denominator, since framerates like 24000/1001 aren't whole
+    pub framerate: Option<(i32, i32)>,
+    /// The raw format the decoder puts out, like `I420`
+    pub format: Option<String>,
+    pub duration: Option<Duration>,
+    pub audio: Vec<Track>,
+    pub subtitles: Vec<Track>,
+}
+
+impl Display for StreamInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unknown = || "unknown".to_string();
+        let size = self.size.map(|(width, height)| format!("{width}x{height}"));
+        writeln!(f, "resolution: {}", size.unwrap_or_else(unknown))?;
+        let framerate = self.framerate.map(|(numer, denom)| match (numer, denom) {
+            // a variable framerate is reported as 0/1
+            (0, _) => "variable".to_string(),
+            (numer, 1) => format!("{numer} fps"),
+            (numer, denom) => format!("{numer}/{denom} ({:.3} fps)", numer as f64 / denom as f64),
+        });
+        writeln!(f, "framerate: {}", framerate.unwrap_or_else(unknown))?;
+        let format = self.format.clone();
+        writeln!(f, "pixel format: {}", format.unwrap_or_else(unknown))?;
+        let duration = self.duration.map(crate::overlay::format_time);
+        writeln!(f, "duration: {}", duration.unwrap_or_else(unknown))?;
+        for (name, tracks) in [("audio", &self.audio), ("subtitle", &self.subtitles)] {
+            write!(f, "{name} tracks:")?;
+            if tracks.is_empty() {
+                write!(f, " none")?;
+            }
+            for (i, track) in tracks.iter().enumerate() {
+                let language = track.language.as_deref().unwrap_or("unknown language");
+                write!(f, "\n  {i}: {language}")?;
+                if let Some(codec) = &track.codec {
+                    write!(f, " ({codec})")?;
+                }
+            }
+            if name == "audio" {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
     }
 }
+
+/// Which decoders `playbin` should prefer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Decoder {
+    /// Let gstreamer pick
+    Auto,
+    Vaapi,
+    Nvdec,
+    /// Never use a hardware decoder
+    Software,
+}
+
+/// Every plugin that provides hardware decoders we know about
+const HARDWARE_PLUGINS: &[&str] = &["va", "vaapi", "nvcodec"];
+
+impl Decoder {
+    fn plugins(self) -> &'static [&'static str] {
+        match self {
+            Self::Vaapi => &["va", "vaapi"],
+            Self::Nvdec => &["nvcodec"],
+            Self::Auto | Self::Software => &[],
+        }
+    }
+    /// Changes the ranks of the decoders in the registry so `playbin` picks the ones we want.
+    /// Falls back to software decoding if none of the requested plugins are installed.
+    fn apply(self) {
+        let registry = gst::Registry::get();
+        let decoders = |plugin: &str| {
+            registry
+                .feature_list_by_plugin(plugin)
+                .into_iter()
+                .filter(|feature| {
+                    feature
+                        .downcast_ref::<gst::ElementFactory>()
+                        .and_then(|factory| factory.metadata(&gst::ELEMENT_METADATA_KLASS))
+                        .is_some_and(|klass| klass.contains("Decoder"))
+                })
+        };
+        let installed: Vec<_> = self
+            .plugins()
+            .iter()
+            .filter(|plugin| registry.find_plugin(plugin).is_some())
+            .collect();
+        let fallback = match self {
+            Self::Auto => return,
+            Self::Software => true,
+            _ if installed.is_empty() => {
+                eprintln!("the {self:?} decoder plugin isn't installed, falling back to software decoding");
+                true
+            }
+            _ => false,
+        };
+        if fallback {
+            for feature in HARDWARE_PLUGINS.iter().flat_map(|plugin| decoders(plugin)) {
+                feature.set_rank(gst::Rank::None);
+            }
+        } else {
+            for feature in installed.into_iter().flat_map(|plugin| decoders(plugin)) {
+                feature.set_rank(gst::Rank::Primary + 100);
+            }
+        }
+    }
+}
+
+/// How many times to restart the video once it ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    Never,
+    Times(u32),
+    Forever,
+}
+
+impl Repeat {
+    /// Uses up one restart, returning whether there was one left
+    fn take(&mut self) -> bool {
+        match self {
+            Self::Never | Self::Times(0) => false,
+            Self::Times(n) => {
+                *n -= 1;
+                true
+            }
+            Self::Forever => true,
+        }
+    }
+}
+
+/// The uris to play back to back and which one is playing
+#[derive(Debug)]
+struct Playlist {
+    uris: Vec<String>,
+    current: usize,
+    /// Restarts from the first uri once the last one ends. A single video is restarted by seeking instead,
+    /// which keeps the playback rate
+    repeat: Repeat,
+}
+
+impl Playlist {
+    /// Moves on to the uri after the current one, wrapping around if there are restarts left
+    fn advance(&mut self) -> Option<&str> {
+        if self.current + 1 < self.uris.len() {
+            self.current += 1;
+        } else if self.repeat.take() {
+            self.current = 0;
+        } else {
+            return None;
+        }
+        Some(&self.uris[self.current])
+    }
+}
+
+/// Where the frames come from
+#[derive(Debug, Clone)]
+pub enum Source {
+    /// Files or urls played back to back by `playbin`, which gets the pipeline tail as its video sink
+    Playbin {
+        /// There must be at least one uri
+        playlist: Vec<String>,
+        /// A description of the element to put before the tail
+        filter: Option<String>,
+    },
+    /// A description of a live source like a camera, which is linked straight to the pipeline tail
+    Live(String),
+}
+
+/// Where playback begins
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StartAt {
+    Time(Duration),
+    /// A fraction of the way through the video from 0 to 1, which is ignored when the length of the video is unknown
+    Fraction(f64),
+}
+
+#[derive(Debug, Clone)]
+pub struct ProducerOptions {
+    /// The maximum amount of time to wait for the decoder to get the source capabilities
+    pub timeout: Duration,
+    pub decoder: Decoder,
+    pub repeat: Repeat,
+    /// Print the warnings posted by the pipeline to standard error
+    pub verbose: bool,
+    /// Where to begin playback instead of the start of the video
+    pub start_at: Option<StartAt>,
+    /// The playback speed, where 0 pauses
+    pub rate: f64,
+    /// How many frames can wait for the renderer before new ones get dropped.
+    /// More frames play smoother when rendering is uneven, but put the video further behind the audio
+    pub buffer: usize,
+    /// How much of a network stream playbin buffers ahead, or its default if `None`
+    pub buffer_duration: Option<Duration>,
+    /// How many milliseconds rtsp streams are buffered to smooth over jitter, or rtspsrc's default if `None`
+    pub rtsp_latency: Option<u32>,
+    /// Only receive rtsp streams over tcp, which gets through firewalls that drop the udp packets
+    pub rtsp_tcp: bool,
+    /// The subtitle track to show, or `None` to leave them off. Only `Source::Playbin` has subtitles
+    pub subtitles: Option<i32>,
+    /// How many samples in a row can fail to be read before playback gives up. The ones before that are skipped, so
+    /// a single broken sample from a flaky stream doesn't end it
+    pub max_bad_samples: u32,
+}
+
 #[derive(Debug)]
 pub struct GstProducer {
+    pipeline: gst::Element,
     sink: AppSink,
     notify: SyncSender<ProducerMessage>,
     recv: Option<Receiver<ProducerMessage>>,
-    frame_data: Arc<Mutex<Vec<u8>>>,
+    // one slot for each frame that can be waiting in the channel, plus the one being rendered and the one being written
+    frame_data: Arc<[Mutex<Vec<u8>>]>,
     counter: Arc<FrameCounter>,
+    finished: Arc<AtomicBool>,
+    // how full the network buffer is while it's filling up, and 100 the rest of the time
+    buffering: Arc<AtomicU8>,
+    // shared with the bus watch so restarting the video keeps the rate
+    rate: Arc<Mutex<f64>>,
+    // whether a rate of 0 paused the video, which the rate itself doesn't change for
+    paused: Arc<AtomicBool>,
+    playlist: Arc<Mutex<Playlist>>,
+    timeout: gst::ClockTime,
+    // the last subtitle that came in
+    subtitle: Arc<Mutex<Option<Cue>>>,
 }
 
 impl GstProducer {
-    pub fn new(pipeline_desc: &str, timeout: Duration) -> Result<Self, Error> {
-        let source = gst::parse_launch(pipeline_desc)?;
-
-        let source = source.downcast::<gst::Bin>().unwrap();
-
-        let video_sink: gst::Element = source.property("video-sink").unwrap().get().unwrap();
-        let pad = video_sink.pads().get(0).cloned().unwrap();
-        let pad = pad.dynamic_cast::<gst::GhostPad>().unwrap();
-        let bin = pad
-            .parent_element()
-            .unwrap()
-            .downcast::<gst::Bin>()
-            .unwrap();
-
-        let app_sink = bin.by_name("app_sink").unwrap();
-        let app_sink = app_sink.downcast::<AppSink>().unwrap();
-
-        let (notify, recv) = sync_channel(1);
-        source.set_state(gst::State::Playing)?;
-        source
-            .state(gst::ClockTime::from_seconds(timeout.as_secs()))
-            .0?;
+    /// Builds the pipeline from `source` and `tail`, which is a description of the elements that scale the video
+    /// and end with an appsink named `app_sink`
+    pub fn new(source: &Source, tail: &str, options: &ProducerOptions) -> Result<Self, Error> {
+        options.decoder.apply();
+        let subtitle = Arc::new(Mutex::new(None));
+        let (source, app_sink, playlist, repeat) = match source {
+            Source::Playbin { playlist, filter } => {
+                let filter = filter
+                    .as_ref()
+                    .map_or_else(String::new, |filter| format!(" video-filter={filter}"));
+                let (source, app_sink) = build_playbin(&format!(
+                    "playbin{filter} video-sink=\"{tail} ! sink_to_location\""
+                ))?;
+                source.set_property("uri", &playlist[0])?;
+                if let Some(duration) = options.buffer_duration {
+                    source.set_property("buffer-duration", duration.as_nanos() as i64)?;
+                }
+                // playbin only makes the source element once it knows the uri's protocol
+                let (latency, tcp) = (options.rtsp_latency, options.rtsp_tcp);
+                source.connect("source-setup", false, move |values| {
+                    let element = values[1].get::<gst::Element>().ok()?;
+                    let is_rtsp = element
+                        .factory()
+                        .is_some_and(|factory| factory.name() == "rtspsrc");
+                    if is_rtsp {
+                        if let Some(latency) = latency {
+                            let _ = element.set_property("latency", latency);
+                        }
+                        if tcp {
+                            element.set_property_from_str("protocols", "tcp");
+                        }
+                    }
+                    None
+                })?;
+                // a single video is restarted by the bus watch, so the playlist only loops when there are more
+                let (repeat, playlist_repeat) = match playlist.len() {
+                    1 => (options.repeat, Repeat::Never),
+                    _ => (Repeat::Never, options.repeat),
+                };
+                let playlist = Arc::new(Mutex::new(Playlist {
+                    uris: playlist.clone(),
+                    current: 0,
+                    repeat: playlist_repeat,
+                }));
+                // queue up the next uri right before the current one ends so there's no gap between them
+                let next = playlist.clone();
+                source.connect("about-to-finish", false, move |values| {
+                    let playbin = values[0].get::<gst::Element>().ok()?;
+                    if let Some(uri) = next.lock().unwrap().advance() {
+                        let _ = playbin.set_property("uri", uri);
+                    }
+                    None
+                })?;
+                if options.subtitles.is_some() {
+                    source.set_property("text-sink", text_sink(subtitle.clone())?)?;
+                }
+                (source, app_sink, playlist, repeat)
+            }
+            Source::Live(description) => {
+                let source = gst::parse_launch(&format!("{description} ! {tail}"))?
+                    .downcast::<gst::Bin>()
+                    .unwrap();
+                let app_sink = source
+                    .by_name("app_sink")
+                    .ok_or("the pipeline has no app_sink")?
+                    .downcast::<AppSink>()
+                    .unwrap();
+                let playlist = Arc::new(Mutex::new(Playlist {
+                    uris: Vec::new(),
+                    current: 0,
+                    repeat: Repeat::Never,
+                }));
+                (source, app_sink, playlist, Repeat::Never)
+            }
+        };
+
+        let (notify, recv) = sync_channel(options.buffer);
+        let timeout = gst::ClockTime::from_seconds(options.timeout.as_secs());
+        // preroll first so we can seek before the first sample is delivered
+        change_state(&source, gst::State::Paused, timeout)?;
+        if let Some(start) = options.start_at {
+            seek_to_start(&source, start)?;
+        }
+        // the tracks are only known once the streams are found
+        if let Some(track) = options
+            .subtitles
+            .filter(|_| source.has_property("current-text", None))
+        {
+            source.set_property("current-text", track)?;
+        }
+        if options.rate != 0. {
+            change_state(&source, gst::State::Playing, timeout)?;
+        }
         let mut this = Self {
+            pipeline: source.upcast(),
             notify,
             recv: Some(recv),
             sink: app_sink,
-            frame_data: Arc::new(Mutex::new(vec![])),
+            frame_data: (0..options.buffer + 2)
+                .map(|_| Mutex::new(Vec::new()))
+                .collect(),
             counter: Arc::new(FrameCounter {
                 dropped: AtomicUsize::new(0),
                 not_dropped: AtomicUsize::new(0),
+                throttled: AtomicUsize::new(0),
+                late: AtomicUsize::new(0),
+                bad: AtomicUsize::new(0),
+                merged: AtomicUsize::new(0),
             }),
+            finished: Arc::new(AtomicBool::new(false)),
+            buffering: Arc::new(AtomicU8::new(100)),
+            rate: Arc::new(Mutex::new(1.)),
+            paused: Arc::new(AtomicBool::new(false)),
+            playlist,
+            timeout,
+            subtitle,
         };
-        this.set_callbacks();
+        if options.rate != 1. {
+            this.set_rate(options.rate)?;
+        }
+        this.set_callbacks(options.max_bad_samples);
+        this.watch_bus(repeat, options.verbose);
         Ok(this)
     }
 
-    fn set_callbacks(&mut self) {
+    /// Handles the messages the pipeline posts on its bus on a separate thread,
+    /// since we don't run a glib main loop
+    fn watch_bus(&self, mut repeat: Repeat, verbose: bool) {
+        let pipeline = self.pipeline.clone();
+        let finished = self.finished.clone();
+        let notify = self.notify.clone();
+        let rate = self.rate.clone();
+        let paused = self.paused.clone();
+        let buffering = self.buffering.clone();
+        let bus = pipeline.bus().expect("pipelines always have a bus");
+        std::thread::spawn(move || {
+            for msg in bus.iter_timed(gst::ClockTime::NONE) {
+                match msg.view() {
+                    gst::MessageView::Eos(..) => {
+                        // the renderer doesn't need to be reinitialized since the dimensions stay the same
+                        let rate = *rate.lock().unwrap();
+                        if repeat.take()
+                            && seek_at_rate(&pipeline, rate, gst::ClockTime::ZERO).is_ok()
+                        {
+                            continue;
+                        }
+                        finished.store(true, Ordering::SeqCst);
+                        let _ = notify.send(ProducerMessage::Eos);
+                        break;
+                    }
+                    gst::MessageView::Error(err) => {
+                        let message =
+                            describe(msg.src().as_ref(), &err.error().to_string(), err.debug());
+                        finished.store(true, Ordering::SeqCst);
+                        let _ = notify.send(ProducerMessage::Error { message });
+                        break;
+                    }
+                    gst::MessageView::Buffering(message) => {
+                        // hold the video until the buffer is full again, unless it was paused anyway
+                        let percent = message.percent().clamp(0, 100) as u8;
+                        buffering.store(percent, Ordering::SeqCst);
+                        if !paused.load(Ordering::SeqCst) {
+                            let state = if percent < 100 {
+                                gst::State::Paused
+                            } else {
+                                gst::State::Playing
+                            };
+                            let _ = pipeline.set_state(state);
+                        }
+                    }
+                    gst::MessageView::Warning(warning) if verbose => {
+                        let message = describe(
+                            msg.src().as_ref(),
+                            &warning.error().to_string(),
+                            warning.debug(),
+                        );
+                        eprintln!("warning: {message}");
+                    }
+                    _ => (),
+                }
+            }
+        });
+    }
+
+    fn set_callbacks(&mut self, max_bad_samples: u32) {
         let notify = self.notify.clone();
         let frame_data = self.frame_data.clone();
         let counter = self.counter.clone();
-        let mut sent_init = false;
+        let mut dims = None;
+        let mut slot = 0;
+        let mut bad_in_a_row = 0;
         self.sink.set_callbacks(
             gst_app::AppSinkCallbacks::builder()
                 .new_sample(move |sink| {
                     let sample = sink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
-                    let buffer = sample.buffer().ok_or(gst::FlowError::Error)?;
-                    let map = buffer.map_readable().map_err(|_| gst::FlowError::Error)?;
-                    {
-                        let mut data = frame_data.lock().map_err(|_| gst::FlowError::Error)?;
-                        if data.is_empty() {
-                            *data = map.to_vec();
-                        } else {
-                            data.copy_from_slice(&map);
+                    let (width, height, pts) = match read_sample(&sample, &frame_data[slot]) {
+                        Ok(frame) => {
+                            bad_in_a_row = 0;
+                            frame
                         }
-                    }
-
-                    {
-                        if !sent_init {
-                            let pad = sink.static_pad("sink").ok_or(gst::FlowError::Error)?;
-
-                            let caps = pad.current_caps().ok_or(gst::FlowError::Error)?;
-                            let s = caps.structure(0).ok_or(gst::FlowError::Error)?;
-                            let width =
-                                s.get::<i32>("width").map_err(|_| gst::FlowError::Error)? as u32;
-                            let height =
-                                s.get::<i32>("height").map_err(|_| gst::FlowError::Error)? as u32;
-                            notify
-                                .send(ProducerMessage::Initialize { width, height })
-                                .map_err(|_| gst::FlowError::Error)?;
-                            sent_init = true; // stop locking every frame after we properly initialize our renderer
+                        Err(message) => {
+                            // skip the sample unless the stream has been broken for a while
+                            counter.bad.fetch_add(1, Ordering::SeqCst);
+                            bad_in_a_row += 1;
+                            if bad_in_a_row > max_bad_samples {
+                                return Err(fail(sink, message));
+                            }
+                            return Ok(gst::FlowSuccess::Ok);
                         }
+                    };
+                    // the next video in the playlist can have different dimensions, so check every sample
+                    if dims != Some((width, height)) {
+                        notify
+                            .send(ProducerMessage::Initialize { width, height })
+                            .map_err(|_| gst::FlowError::Error)?;
+                        dims = Some((width, height));
                     }
-                    match notify.try_send(ProducerMessage::FrameReady) {
+                    // when the channel is full, the slot is overwritten by the next frame
+                    match notify.try_send(ProducerMessage::FrameReady { slot, pts }) {
                         Err(std::sync::mpsc::TrySendError::Full(_)) => {
                             counter
                                 .dropped
@@ -132,7 +600,7 @@ impl GstProducer {
                             return Ok(gst::FlowSuccess::Ok);
                         }
                         Err(_) => return Err(gst::FlowError::Error),
-                        _ => (),
+                        _ => slot = (slot + 1) % frame_data.len(),
                     }
                     counter
                         .not_dropped
@@ -142,18 +610,441 @@ impl GstProducer {
                 .build(),
         )
     }
+    /// Changes the playback speed, where a rate of 0 pauses and any other rate resumes playback.
+    /// Pausing keeps the rate from before, and [`is_paused`](Self::is_paused) tells the two apart.
+    /// Audio is muted at rates far from 1, since most audio sinks don't handle those well.
+    pub fn set_rate(&self, rate: f64) -> Result<(), Error> {
+        if rate == 0. {
+            self.pipeline.set_state(gst::State::Paused)?;
+            self.paused.store(true, Ordering::SeqCst);
+            return Ok(());
+        }
+        if rate < 0. || !rate.is_finite() {
+            return Err(format!("the playback rate must be a positive number, not {rate}").into());
+        }
+        let _ = self
+            .pipeline
+            .set_property("mute", !(0.5..=2.).contains(&rate));
+        let (_, state, _) = self.pipeline.state(gst::ClockTime::ZERO);
+        // gstreamer 1.18 can change the rate without flushing, but our bindings only expose
+        // the flag behind a feature, so check the runtime version instead
+        let instant = unsafe {
+            gst::SeekFlags::from_bits_unchecked(gst::ffi::GST_SEEK_FLAG_INSTANT_RATE_CHANGE)
+        };
+        let (major, minor, ..) = gst::version();
+        let changed = (major, minor) >= (1, 18)
+            && self
+                .pipeline
+                .seek(
+                    rate,
+                    instant,
+                    gst::SeekType::None,
+                    gst::ClockTime::NONE,
+                    gst::SeekType::None,
+                    gst::ClockTime::NONE,
+                )
+                .is_ok();
+        if !changed {
+            let position = self
+                .pipeline
+                .query_position::<gst::ClockTime>()
+                .unwrap_or(gst::ClockTime::ZERO);
+            seek_at_rate(&self.pipeline, rate, position)?;
+        }
+        *self.rate.lock().unwrap() = rate;
+        self.paused.store(false, Ordering::SeqCst);
+        if state != gst::State::Playing {
+            self.pipeline.set_state(gst::State::Playing)?;
+        }
+        Ok(())
+    }
+    /// The playback speed, which is kept while paused
+    pub fn rate(&self) -> f64 {
+        *self.rate.lock().unwrap()
+    }
+    /// Whether a rate of 0 paused the video
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+    /// The volume of the audio from 0 to 1, or `None` for live sources, which don't play audio
+    pub fn volume(&self) -> Option<f64> {
+        self.pipeline.property("volume").ok()?.get().ok()
+    }
+    /// Changes the volume of the audio, clamped to 0 to 1
+    pub fn set_volume(&self, volume: f64) -> Result<(), Error> {
+        self.pipeline.set_property("volume", volume.clamp(0., 1.))?;
+        Ok(())
+    }
+    /// Jumps to `position` in the current video, keeping the playback rate
+    pub fn seek(&self, position: Duration) -> Result<(), Error> {
+        let rate = *self.rate.lock().unwrap();
+        let position = gst::ClockTime::from_nseconds(position.as_nanos() as u64);
+        seek_at_rate(&self.pipeline, rate, position)?;
+        Ok(())
+    }
+    /// Skips to the next video in the playlist, doing nothing on the last one
+    pub fn next(&self) -> Result<(), Error> {
+        let (current, len) = {
+            let playlist = self.playlist.lock().unwrap();
+            (playlist.current, playlist.uris.len())
+        };
+        if current + 1 < len {
+            self.switch_to(current + 1)?;
+        }
+        Ok(())
+    }
+    /// Goes back to the previous video in the playlist, or restarts the first one
+    pub fn previous(&self) -> Result<(), Error> {
+        let (current, len) = {
+            let playlist = self.playlist.lock().unwrap();
+            (playlist.current, playlist.uris.len())
+        };
+        // live sources don't have a playlist to go back in
+        if len > 0 {
+            self.switch_to(current.saturating_sub(1))?;
+        }
+        Ok(())
+    }
+    /// Starts playing the video at `index` in the playlist from the beginning, keeping the playback rate
+    fn switch_to(&self, index: usize) -> Result<(), Error> {
+        let uri = {
+            let mut playlist = self.playlist.lock().unwrap();
+            playlist.current = index;
+            playlist.uris[index].clone()
+        };
+        // playbin only picks up a new uri when it is stopped
+        self.pipeline.set_state(gst::State::Ready)?;
+        self.pipeline.set_property("uri", &uri)?;
+        self.pipeline.set_state(gst::State::Paused)?;
+        self.pipeline.state(self.timeout).0?;
+        // a paused video stays paused on the new one
+        let rate = if self.is_paused() {
+            0.
+        } else {
+            *self.rate.lock().unwrap()
+        };
+        self.set_rate(rate)
+    }
+    /// Changes the caps the frames are scaled to, like after the terminal is resized.
+    /// A new `Initialize` is sent once the frames come in at the new size
+    pub fn resize(&self, caps: &str) -> Result<(), Error> {
+        let caps: gst::Caps = caps.parse()?;
+        self.sink.set_caps(Some(&caps));
+        // ask the elements before the sink to pick new caps
+        if let Some(pad) = self.sink.static_pad("sink") {
+            pad.push_event(gst::event::Reconfigure::new());
+        }
+        Ok(())
+    }
+    /// Changes the aspect ratio of the aspectratiocrop element named `name`, which is cropped to right away
+    pub fn set_crop_aspect(&self, name: &str, (numer, denom): (u32, u32)) -> Result<(), Error> {
+        let crop = self
+            .pipeline
+            .downcast_ref::<gst::Bin>()
+            .and_then(|bin| bin.by_name(name))
+            .ok_or_else(|| format!("the pipeline has no {name}"))?;
+        crop.set_property(
+            "aspect-ratio",
+            gst::Fraction::new(numer as i32, denom as i32),
+        )?;
+        Ok(())
+    }
+    /// The length of the video, which is unknown for live streams
+    pub fn duration(&self) -> Option<Duration> {
+        self.pipeline
+            .query_duration::<gst::ClockTime>()
+            .map(|t| Duration::from_nanos(t.nseconds()))
+    }
+    /// How far into the video playback currently is
+    pub fn position(&self) -> Option<Duration> {
+        self.pipeline
+            .query_position::<gst::ClockTime>()
+            .map(|t| Duration::from_nanos(t.nseconds()))
+    }
+    /// How full the network buffer is while playback waits for it, or `None` when it isn't buffering
+    pub fn buffering(&self) -> Option<u8> {
+        let percent = self.buffering.load(Ordering::SeqCst);
+        (percent < 100).then_some(percent)
+    }
     pub fn counter(&self) -> Arc<FrameCounter> {
         self.counter.clone()
     }
+    /// Asks the pipeline what it found in the video. The tracks are only known with playbin, and live pipelines only
+    /// have the caps after they were scaled to the terminal
+    pub fn probe(&self) -> StreamInfo {
+        let pad = self
+            .pipeline
+            .emit_by_name("get-video-pad", &[&0i32])
+            .ok()
+            .flatten()
+            .and_then(|pad| pad.get::<Option<gst::Pad>>().ok().flatten())
+            .or_else(|| self.sink.static_pad("sink"));
+        let caps = pad.and_then(|pad| pad.current_caps());
+        let structure = caps.as_ref().and_then(|caps| caps.structure(0));
+        let field = |name| structure.and_then(|s| s.get::<i32>(name).ok());
+        StreamInfo {
+            size: field("width").zip(field("height")),
+            framerate: structure
+                .and_then(|s| s.get::<gst::Fraction>("framerate").ok())
+                .map(|rate| (*rate.numer(), *rate.denom())),
+            format: structure.and_then(|s| s.get::<String>("format").ok()),
+            duration: self.duration(),
+            audio: self.tracks("n-audio", "get-audio-tags", |tags| {
+                tags.get::<gst::tags::AudioCodec>()
+                    .map(|codec| codec.get().to_string())
+            }),
+            subtitles: self.tracks("n-text", "get-text-tags", |tags| {
+                tags.get::<gst::tags::SubtitleCodec>()
+                    .map(|codec| codec.get().to_string())
+            }),
+        }
+    }
+    /// The tracks of one kind in playbin, with `count` being the property with how many there are and `tags` the
+    /// signal for the tags of each one
+    fn tracks(
+        &self,
+        count: &str,
+        tags: &str,
+        codec: impl Fn(&gst::TagList) -> Option<String>,
+    ) -> Vec<Track> {
+        if !self.pipeline.has_property(count, None) {
+            return Vec::new();
+        }
+        let count = self
+            .pipeline
+            .property(count)
+            .ok()
+            .and_then(|count| count.get::<i32>().ok())
+            .unwrap_or(0);
+        (0..count)
+            .map(|i| {
+                let tags = self
+                    .pipeline
+                    .emit_by_name(tags, &[&i])
+                    .ok()
+                    .flatten()
+                    .and_then(|tags| tags.get::<Option<gst::TagList>>().ok().flatten());
+                tags.map_or_else(Track::default, |tags| Track {
+                    language: tags
+                        .get::<gst::tags::LanguageCode>()
+                        .map(|language| language.get().to_string()),
+                    codec: codec(&tags),
+                })
+            })
+            .collect()
+    }
+    /// The text of the subtitle that is on the screen right now, if there is one
+    pub fn subtitle(&self) -> Option<String> {
+        let position = self.position()?;
+        let subtitle = self.subtitle.lock().unwrap();
+        subtitle
+            .as_ref()
+            .filter(|cue| cue.is_active(position))
+            .map(|cue| cue.text.clone())
+    }
+}
+
+/// An appsink for playbin's subtitles that keeps the last one in `subtitle`.
+/// It syncs to the clock like the video, so each subtitle arrives when it should be shown
+fn text_sink(subtitle: Arc<Mutex<Option<Cue>>>) -> Result<AppSink, Error> {
+    let sink = gst::ElementFactory::make("appsink", Some("text_sink"))?
+        .downcast::<AppSink>()
+        .unwrap();
+    sink.set_caps(Some(&"text/x-raw".parse()?));
+    // subtitles are sparse, so don't hold up prerolling waiting for the first one
+    sink.set_property("async", false)?;
+    sink.set_callbacks(
+        gst_app::AppSinkCallbacks::builder()
+            .new_sample(move |sink| {
+                let sample = sink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                let Some(buffer) = sample.buffer() else {
+                    return Ok(gst::FlowSuccess::Ok);
+                };
+                let segment = sample.segment();
+                let segment = segment
+                    .as_ref()
+                    .and_then(|segment| segment.downcast_ref::<gst::ClockTime>());
+                // the timestamps are in the segment, which starts over after a seek, while the position isn't
+                let to_position = |time: gst::ClockTime| {
+                    let time = segment
+                        .and_then(|segment| segment.to_stream_time(time))
+                        .unwrap_or(time);
+                    Duration::from_nanos(time.nseconds())
+                };
+                let (Some(pts), Ok(map)) = (buffer.pts(), buffer.map_readable()) else {
+                    return Ok(gst::FlowSuccess::Ok);
+                };
+                // the text is pango markup unless the subtitles were plain to begin with, and plain text has no entities
+                let text = strip_tags(&String::from_utf8_lossy(&map))
+                    .replace("&lt;", "<")
+                    .replace("&gt;", ">")
+                    .replace("&quot;", "\"")
+                    .replace("&apos;", "'")
+                    .replace("&amp;", "&");
+                *subtitle.lock().unwrap() = Some(Cue {
+                    start: to_position(pts),
+                    end: buffer
+                        .duration()
+                        .map(|duration| to_position(pts + duration)),
+                    text,
+                });
+                Ok(gst::FlowSuccess::Ok)
+            })
+            .build(),
+    );
+    Ok(sink)
+}
+
+/// Finds the appsink inside of the bin playbin was given as its video sink
+fn build_playbin(description: &str) -> Result<(gst::Bin, AppSink), Error> {
+    let source = gst::parse_launch(description)?;
+    let source = source.downcast::<gst::Bin>().unwrap();
+
+    let video_sink: gst::Element = source.property("video-sink").unwrap().get().unwrap();
+    let pad = video_sink.pads().first().cloned().unwrap();
+    let pad = pad.dynamic_cast::<gst::GhostPad>().unwrap();
+    let bin = pad
+        .parent_element()
+        .unwrap()
+        .downcast::<gst::Bin>()
+        .unwrap();
+
+    let app_sink = bin.by_name("app_sink").unwrap();
+    let app_sink = app_sink.downcast::<AppSink>().unwrap();
+    Ok((source, app_sink))
+}
+
+/// Changes the state of the pipeline and waits for it. When that fails, the reason is usually an error on the bus,
+/// like a camera that doesn't exist, which says a lot more than the state change error
+fn change_state(
+    pipeline: &gst::Bin,
+    state: gst::State,
+    timeout: gst::ClockTime,
+) -> Result<(), Error> {
+    let result = pipeline
+        .set_state(state)
+        .and_then(|_| pipeline.state(timeout).0);
+    if let Err(err) = result {
+        let message = pipeline
+            .bus()
+            .and_then(|bus| bus.pop_filtered(&[gst::MessageType::Error]));
+        if let Some(msg) = message {
+            if let gst::MessageView::Error(error) = msg.view() {
+                return Err(describe(
+                    msg.src().as_ref(),
+                    &error.error().to_string(),
+                    error.debug(),
+                )
+                .into());
+            }
+        }
+        return Err(err.into());
+    }
+    Ok(())
+}
+
+fn seek_to_start(pipeline: &gst::Bin, start: StartAt) -> Result<(), Error> {
+    let duration = pipeline.query_duration::<gst::ClockTime>();
+    let start = match (start, duration) {
+        (StartAt::Time(start), _) => gst::ClockTime::from_nseconds(start.as_nanos() as u64),
+        (StartAt::Fraction(fraction), Some(duration)) => gst::ClockTime::from_nseconds(
+            (duration.nseconds() as f64 * fraction.clamp(0., 1.)) as u64,
+        ),
+        (StartAt::Fraction(_), None) => return Ok(()),
+    };
+    if let Some(duration) = duration {
+        if start > duration {
+            return Err(format!(
+                "the start time {start} is past the end of the video ({duration})"
+            )
+            .into());
+        }
+    }
+    pipeline.seek_simple(gst::SeekFlags::FLUSH | gst::SeekFlags::KEY_UNIT, start)?;
+    Ok(())
+}
+
+/// A flushing seek that also sets the playback rate, since `seek_simple` always resets it to 1
+fn seek_at_rate(
+    pipeline: &gst::Element,
+    rate: f64,
+    position: gst::ClockTime,
+) -> Result<(), glib::BoolError> {
+    pipeline.seek(
+        rate,
+        gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+        gst::SeekType::Set,
+        Some(position),
+        gst::SeekType::None,
+        gst::ClockTime::NONE,
+    )
+}
+
+/// Copies `frame` into a slot, reusing the allocation from the frame that was there before so playing at the same
+/// size doesn't allocate. This also handles the frame size changing, where `copy_from_slice` would panic
+pub fn copy_frame(slot: &mut Vec<u8>, frame: &[u8]) {
+    slot.clear();
+    slot.extend_from_slice(frame);
+}
+
+/// Copies the frame in `sample` into `data`, returning its width, height, and timestamp, or what's wrong with the
+/// sample if it can't be read
+fn read_sample(
+    sample: &gst::Sample,
+    data: &Mutex<Vec<u8>>,
+) -> Result<(u32, u32, Option<Duration>), &'static str> {
+    let buffer = sample.buffer().ok_or("the sample has no buffer")?;
+    let caps = sample.caps().ok_or("the sample has no caps")?;
+    let s = caps.structure(0).ok_or("the caps are empty")?;
+    let width = s
+        .get::<i32>("width")
+        .map_err(|_| "the caps have no width")? as u32;
+    let height = s
+        .get::<i32>("height")
+        .map_err(|_| "the caps have no height")? as u32;
+    let map = buffer
+        .map_readable()
+        .map_err(|_| "failed to map the buffer")?;
+    let mut data = data.lock().map_err(|_| "the frame buffer was poisoned")?;
+    copy_frame(&mut data, &map);
+    let pts = buffer.pts().map(|pts| Duration::from_nanos(pts.nseconds()));
+    Ok((width, height, pts))
+}
+
+/// Posts an error on the bus so the render loop can report it, returning the flow error for the callback to return
+fn fail(sink: &AppSink, message: &str) -> gst::FlowError {
+    gst::element_error!(sink, gst::StreamError::Failed, (message));
+    gst::FlowError::Error
+}
+
+/// Formats an error or warning from the bus along with the element that posted it
+fn describe(src: Option<&gst::Object>, error: &str, debug: Option<String>) -> String {
+    let src = src.map_or_else(
+        || "pipeline".to_owned(),
+        |src| src.path_string().to_string(),
+    );
+    match debug {
+        Some(debug) => format!("{src}: {error}\n{debug}"),
+        None => format!("{src}: {error}"),
+    }
 }
 
 impl Producer for GstProducer {
-    fn frame(&self) -> Option<MutexGuard<Vec<u8>>> {
-        Some(self.frame_data.lock().unwrap())
+    fn swap_frame(&self, slot: usize, frame: &mut Vec<u8>) -> bool {
+        match self.frame_data.get(slot) {
+            Some(data) => {
+                std::mem::swap(&mut *data.lock().unwrap(), frame);
+                true
+            }
+            None => false,
+        }
     }
     fn subscribe(&mut self) -> Receiver<ProducerMessage> {
         self.recv
             .take()
             .expect("only a single subscriber can be subscribed to this producer")
     }
+    fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::SeqCst)
+    }
 }
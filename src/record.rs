@@ -0,0 +1,169 @@
+use std::{
+    cell::Cell,
+    io::{self, BufRead, Write},
+    rc::Rc,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// The first line of a recording, so replaying something else fails right away
+const MAGIC: &[u8] = b"onna recording 1\n";
+
+/// Passes everything through to `inner` and saves it to `file` along with when it was flushed.
+///
+/// The render loop flushes once per frame, so every entry is one frame: the microseconds since the recording started
+/// and the length of the frame as little endian `u64` and `u32`, followed by the frame itself
+pub struct Recorder<W: Write, F: Write> {
+    inner: W,
+    file: F,
+    pending: Vec<u8>,
+    start: Instant,
+}
+
+impl<W: Write, F: Write> Recorder<W, F> {
+    pub fn new(inner: W, mut file: F) -> io::Result<Self> {
+        file.write_all(MAGIC)?;
+        Ok(Self {
+            inner,
+            file,
+            pending: Vec::new(),
+            start: Instant::now(),
+        })
+    }
+}
+
+impl<W: Write, F: Write> Write for Recorder<W, F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.pending.extend_from_slice(&buf[..written]);
+        Ok(written)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()?;
+        if !self.pending.is_empty() {
+            let micros = self.start.elapsed().as_micros() as u64;
+            self.file.write_all(&micros.to_le_bytes())?;
+            self.file
+                .write_all(&(self.pending.len() as u32).to_le_bytes())?;
+            self.file.write_all(&self.pending)?;
+            self.pending.clear();
+        }
+        self.file.flush()
+    }
+}
+
+/// Writes a recording made by [`Recorder`] to `out` with the same timing, stopping early once `stop` returns true
+pub fn replay(
+    mut recording: impl BufRead,
+    mut out: impl Write,
+    mut stop: impl FnMut() -> bool,
+) -> io::Result<()> {
+    let mut magic = vec![0; MAGIC.len()];
+    recording.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the file is not an onna recording",
+        ));
+    }
+    let start = Instant::now();
+    let mut frame = Vec::new();
+    while !recording.fill_buf()?.is_empty() && !stop() {
+        let mut header = [0; 12];
+        let read = recording.read_exact(&mut header).and_then(|_| {
+            let len = u32::from_le_bytes(header[8..].try_into().unwrap());
+            frame.resize(len as usize, 0);
+            recording.read_exact(&mut frame)
+        });
+        match read {
+            // a recording that was cut off, like when onna was killed, just ends at the last whole frame
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+            result => result?,
+        }
+        let micros = u64::from_le_bytes(header[..8].try_into().unwrap());
+
+        let at = Duration::from_micros(micros);
+        if let Some(wait) = at.checked_sub(start.elapsed()) {
+            thread::sleep(wait);
+        }
+        out.write_all(&frame)?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+/// Passes everything through to `inner` and saves it to `file` as an asciinema v2 cast, with an output event for
+/// every flush
+pub struct CastWriter<W: Write, F: Write> {
+    inner: W,
+    file: F,
+    pending: Vec<u8>,
+    start: Instant,
+    // set by the closure from `resizer` and written out with the next frame
+    resized: Rc<Cell<Option<(u16, u16)>>>,
+}
+
+impl<W: Write, F: Write> CastWriter<W, F> {
+    /// Writes the header for a terminal that is `(width, height)` cells big
+    pub fn new(inner: W, mut file: F, (width, height): (u16, u16)) -> io::Result<Self> {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        writeln!(
+            file,
+            r#"{{"version": 2, "width": {width}, "height": {height}, "timestamp": {timestamp}}}"#
+        )?;
+        Ok(Self {
+            inner,
+            file,
+            pending: Vec::new(),
+            start: Instant::now(),
+            resized: Rc::new(Cell::new(None)),
+        })
+    }
+    /// A callback for [`Player::run_with_resize`](crate::Player::run_with_resize) that adds a resize event to the cast
+    pub fn resizer(&self) -> impl FnMut((u16, u16)) {
+        let resized = self.resized.clone();
+        move |size| resized.set(Some(size))
+    }
+}
+
+/// `bytes` as a JSON string
+fn json_string(bytes: &[u8]) -> String {
+    let mut json = String::with_capacity(bytes.len() + 2);
+    json.push('"');
+    for c in String::from_utf8_lossy(bytes).chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+impl<W: Write, F: Write> Write for CastWriter<W, F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.pending.extend_from_slice(&buf[..written]);
+        Ok(written)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()?;
+        let time = self.start.elapsed().as_secs_f64();
+        if let Some((width, height)) = self.resized.take() {
+            writeln!(self.file, r#"[{time:.6}, "r", "{width}x{height}"]"#)?;
+        }
+        if !self.pending.is_empty() {
+            let data = json_string(&self.pending);
+            writeln!(self.file, r#"[{time:.6}, "o", {data}]"#)?;
+            self.pending.clear();
+        }
+        self.file.flush()
+    }
+}
@@ -1,38 +1,417 @@
 use std::{
+    fmt::Display,
     io::{self, Write},
     marker::PhantomData,
     ops::Range,
     slice::from_raw_parts,
+    sync::Arc,
 };
 
 use base64ct::{Base64, Encoding};
+use flate2::{write::ZlibEncoder, Compression};
 
 use crate::{
-    buffer::Differ,
-    color::{Ansi256, BackgroundAnsi256, BackgroundRgb, Colorize, Rgb},
+    buffer::{dirty_rect, Differ, Rect},
+    color::{
+        luminance, luminance_chunk, Ansi16, Ansi256, BackgroundAnsi16, BackgroundAnsi256,
+        BackgroundGrayscale, BackgroundRgb, Colorize, Grayscale, HalfBlock, LumaWeights, NoColor,
+        Palette, Rgb, LUMINANCE_CHUNK,
+    },
 };
 
 pub type Pixel = [u8; 4];
 
+/// User-tunable options shared by all renderers
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Added to each normalized channel after the contrast is applied
+    pub brightness: f32,
+    /// Scales each normalized channel around the midpoint
+    pub contrast: f32,
+    /// Gamma applied to the emitted colors, or the renderer's own default if `None`
+    pub gamma: Option<f32>,
+    /// Inverts every channel before any other adjustment
+    pub invert: bool,
+    /// Repeat runs of the same character with the REP escape, which not every terminal supports
+    pub use_rep: bool,
+    /// The number of terminal rows above the video, which are reserved for overlays or centering the video
+    pub row_offset: u32,
+    /// The number of terminal columns left of the video, for centering it
+    pub col_offset: u32,
+    /// The number of columns and rows the video is drawn in, for the image protocols that scale the image to fit
+    pub cells: (u32, u32),
+    /// Compress the kitty image data with zlib before sending it
+    pub compress: bool,
+    /// How many bytes of base64 go in each kitty escape. This has to be a multiple of 4 so each chunk decodes on its own
+    pub kitty_chunk: usize,
+    /// How to dither the colors before they are quantized to the 16 or 256 color palette
+    pub dither: Dither,
+    /// Flip the colors between the two nearest levels of the 16 or 256 color palette on every other frame, so the eye
+    /// sees the color between them. This goes on top of `dither`
+    pub temporal_dither: bool,
+    /// Pick the characters by how strong the edges are instead of how bright the pixels are
+    pub edges: Edges,
+    /// How much each channel counts towards the brightness that picks the characters in ascii mode
+    pub luma_weights: LumaWeights,
+    /// Pixels darker than this are drawn as black spaces in ascii mode, so dark areas are flat and cheap to draw
+    pub black_threshold: u8,
+    /// How far apart in each channel neighboring colors can be and still be drawn as one stride in the color of the
+    /// first, for the character modes. 0 only joins equal colors
+    pub color_tolerance: u8,
+    /// How far a cell's color can move in each channel between frames without being redrawn, for the character modes.
+    /// 0 redraws every change
+    pub motion_threshold: u8,
+    /// The colors the truecolor character modes are quantized to, which are still written as truecolor so they come
+    /// out exactly as given
+    pub palette: Option<Arc<Palette>>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            brightness: 0.,
+            contrast: 1.,
+            gamma: None,
+            invert: false,
+            use_rep: false,
+            row_offset: 0,
+            col_offset: 0,
+            cells: (80, 24),
+            compress: true,
+            kitty_chunk: KITTY_CHUNK,
+            dither: Dither::None,
+            temporal_dither: false,
+            edges: Edges::Off,
+            luma_weights: LumaWeights::DEFAULT,
+            black_threshold: 0,
+            color_tolerance: 0,
+            motion_threshold: 0,
+            palette: None,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// The brightness and contrast adjustment as a lookup table
+    fn adjust_lut(&self) -> ChannelLut {
+        ChannelLut::new(|x| self.adjust(x))
+    }
+    /// The brightness, contrast, and gamma adjustments combined into a single lookup table
+    fn color_lut(&self, default_gamma: f32) -> ChannelLut {
+        let gamma = self.gamma.unwrap_or(default_gamma);
+        ChannelLut::new(|x| gamma_correct(self.adjust(x), gamma))
+    }
+    #[allow(clippy::cast_possible_truncation)]
+    fn adjust(&self, x: u8) -> u8 {
+        let x = if self.invert { u8::MAX - x } else { x };
+        let x = x as f32 / 255.;
+        let x = (x - 0.5) * self.contrast + 0.5 + self.brightness;
+        (x * 255.).round().clamp(0., u8::MAX as _) as u8
+    }
+}
+
+/// How to hide the banding from quantizing to 16 or 256 colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Dither {
+    None,
+    /// Ordered dithering with an 8x8 Bayer matrix, which is nearly free and doesn't flicker
+    Bayer,
+    /// Floyd-Steinberg error diffusion, which looks the best on photographic video but costs more cpu.
+    /// The error spreads across the frame, so small changes ripple out into more changed cells to redraw
+    Fs,
+}
+
+/// Whether the characters in ascii mode trace the edges of the video, and what color they are when they do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edges {
+    Off,
+    /// The characters keep the color of their pixel
+    Colored,
+    /// Every character is white, like a line drawing
+    Mono,
+}
+
+/// The 8x8 Bayer threshold matrix
+const BAYER: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// Nudges each pixel by its threshold in the Bayer matrix so gradients turn into patterns instead of bands.
+/// The offsets span `step`, the distance between the levels the colors get quantized to
+fn dither_bayer(pixels: &mut [Pixel], width: usize, step: i16) {
+    if step == 0 {
+        return;
+    }
+    for (y, row) in pixels.chunks_mut(width).enumerate() {
+        let thresholds = &BAYER[y % 8];
+        for (x, pixel) in row.iter_mut().enumerate() {
+            // centered on 0 so the image doesn't get brighter on average
+            let offset = (thresholds[x % 8] as i16 * 2 - 63) * step / 128;
+            for channel in &mut pixel[..3] {
+                *channel = (*channel as i16 + offset).clamp(0, 255) as u8;
+            }
+        }
+    }
+}
+
+/// Nudges every pixel up or down by a quarter of `step` in a checkerboard that flips with `odd_frame`, so colors
+/// between two levels alternate between both of them from frame to frame instead of always rounding to the same one
+fn dither_temporal(pixels: &mut [Pixel], width: usize, step: i16, odd_frame: bool) {
+    if step == 0 {
+        return;
+    }
+    for (y, row) in pixels.chunks_mut(width).enumerate() {
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let offset = if (x + y) % 2 == odd_frame as usize {
+                step / 4
+            } else {
+                -step / 4
+            };
+            for channel in &mut pixel[..3] {
+                *channel = (*channel as i16 + offset).clamp(0, 255) as u8;
+            }
+        }
+    }
+}
+
+/// Diffuses the error from quantizing each pixel into its neighbors with the Floyd-Steinberg weights.
+/// `errors` is scratch space for two rows of accumulated error, and `palette` is the one the colors get quantized to
+fn dither_floyd_steinberg<C: Colorize>(
+    pixels: &mut [Pixel],
+    width: usize,
+    errors: &mut Vec<[i16; 3]>,
+    palette: Option<&Palette>,
+) {
+    errors.clear();
+    errors.resize(width * 2, [0; 3]);
+    let (mut current, mut next) = errors.split_at_mut(width);
+    for row in pixels.chunks_mut(width) {
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let mut wanted = [0; 3];
+            for (channel, (&value, error)) in pixel[..3].iter().zip(current[x]).enumerate() {
+                wanted[channel] = (value as i16 + error / 16).clamp(0, 255) as u8;
+            }
+            pixel[..3].copy_from_slice(&wanted);
+            let got = C::from_rgb_in(wanted, palette).to_rgb();
+            for channel in 0..3 {
+                let error = wanted[channel] as i16 - got[channel] as i16;
+                if x + 1 < width {
+                    current[x + 1][channel] += error * 7;
+                    next[x + 1][channel] += error;
+                }
+                if x > 0 {
+                    next[x - 1][channel] += error * 3;
+                }
+                next[x][channel] += error * 5;
+            }
+        }
+        std::mem::swap(&mut current, &mut next);
+        next.fill([0; 3]);
+    }
+}
+
+/// Calls `edge` with the index and the strength of the edge at every pixel of a `width` pixel wide luminance image,
+/// found by convolving it with the Sobel kernels. The pixels past the border are copies of the ones on it
+fn sobel(lums: &[u8], width: usize, mut edge: impl FnMut(usize, u8)) {
+    if width == 0 {
+        return;
+    }
+    let height = lums.len() / width;
+    let at = |x: usize, y: usize| lums[y * width + x] as i32;
+    for y in 0..height {
+        let (up, down) = (y.saturating_sub(1), (y + 1).min(height - 1));
+        for x in 0..width {
+            let (left, right) = (x.saturating_sub(1), (x + 1).min(width - 1));
+            let gx = at(right, up) + 2 * at(right, y) + at(right, down)
+                - at(left, up)
+                - 2 * at(left, y)
+                - at(left, down);
+            let gy = at(left, down) + 2 * at(x, down) + at(right, down)
+                - at(left, up)
+                - 2 * at(x, up)
+                - at(right, up);
+            // |gx| + |gy| is a cheap stand-in for the length of the gradient, and tops out at 2040
+            let magnitude = ((gx.abs() + gy.abs()) / 4).min(u8::MAX as _);
+            edge(y * width + x, magnitude as u8);
+        }
+    }
+}
+
+/// A precomputed mapping for a single color channel, so we don't call `powf` per channel per pixel
+#[derive(Clone)]
+struct ChannelLut {
+    table: [u8; 256],
+    // worked out once here, since it's checked for every frame to skip the mapping
+    identity: bool,
+}
+
+impl ChannelLut {
+    #[allow(clippy::cast_possible_truncation)]
+    fn new(f: impl Fn(u8) -> u8) -> Self {
+        let mut table = [0u8; 256];
+        for (i, v) in table.iter_mut().enumerate() {
+            *v = f(i as u8);
+        }
+        let identity = table.iter().enumerate().all(|(i, &v)| i == v as usize);
+        Self { table, identity }
+    }
+    fn is_identity(&self) -> bool {
+        self.identity
+    }
+    /// Maps the color channels, leaving the character byte alone
+    fn apply(&self, pixel: Pixel) -> Pixel {
+        let [r, g, b, c] = pixel;
+        [
+            self.table[r as usize],
+            self.table[g as usize],
+            self.table[b as usize],
+            c,
+        ]
+    }
+}
+
+/// How many pixels each rayon task maps at least, so the cost of splitting the work doesn't outweigh it on small
+/// terminals
+#[cfg(feature = "parallel")]
+const MIN_PARALLEL_PIXELS: usize = 4096;
+
+/// Maps `src` into `dst` in chunks of up to `LUMINANCE_CHUNK` pixels, across all cores with the `parallel` feature
+fn map_chunks(dst: &mut [Pixel], src: &[Pixel], f: impl Fn(&mut [Pixel], &[Pixel]) + Sync) {
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        dst.par_chunks_mut(LUMINANCE_CHUNK)
+            .zip(src.par_chunks(LUMINANCE_CHUNK))
+            .with_min_len(MIN_PARALLEL_PIXELS / LUMINANCE_CHUNK)
+            .for_each(|(dst, src)| f(dst, src));
+    }
+    #[cfg(not(feature = "parallel"))]
+    for (dst, src) in dst
+        .chunks_mut(LUMINANCE_CHUNK)
+        .zip(src.chunks(LUMINANCE_CHUNK))
+    {
+        f(dst, src);
+    }
+}
+
+/// Maps every pixel of `src` into `dst`
+fn map_pixels(dst: &mut [Pixel], src: &[Pixel], f: impl Fn(Pixel) -> Pixel + Sync) {
+    map_chunks(dst, src, |dst, src| {
+        for (dst, pixel) in dst.iter_mut().zip(src) {
+            *dst = f(*pixel);
+        }
+    });
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderError {
+    /// The frame doesn't match the renderer's dimensions, which happens for a frame or two after a resize
+    FrameSize { expected: usize, got: usize },
+}
+
+impl Display for RenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FrameSize { expected, got } => {
+                write!(f, "expected a frame of {expected} bytes, but got {got}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
 pub trait Renderer {
     type State;
-    fn from_dims(width: u32, height: u32) -> Self;
+    fn from_dims(width: u32, height: u32, options: &RenderOptions) -> Self;
     fn create_state(&self) -> Self::State;
     fn width(&self) -> u32;
     fn height(&self) -> u32;
     fn consume(&mut self, data: &[Pixel]);
+    /// Makes the next frame that is consumed draw all of itself instead of only what changed, for when the screen
+    /// can't be trusted to still hold the last frame
+    fn invalidate(&mut self, state: &mut Self::State);
 
     fn render_frame(&self, output: &mut impl Write, state: &mut Self::State) -> io::Result<()>;
 
-    fn verify_input<'a>(&self, data: &'a [u8]) -> &'a [Pixel] {
-        let area = self.width() * self.height();
-        assert_eq!(data.len() as u32, area * 4);
+    /// Checks a frame straight from the decoder against the renderer's dimensions and consumes it. Renderers that
+    /// take another format than RGBx override this
+    fn consume_frame(&mut self, data: &[u8]) -> Result<(), RenderError> {
+        let pixels = self.verify_input(data)?;
+        self.consume(pixels);
+        Ok(())
+    }
+
+    fn verify_input<'a>(&self, data: &'a [u8]) -> Result<&'a [Pixel], RenderError> {
+        let area = self.width() as usize * self.height() as usize;
+        if data.len() != area * 4 {
+            return Err(RenderError::FrameSize {
+                expected: area * 4,
+                got: data.len(),
+            });
+        }
         let ptr = data.as_ptr().cast::<[u8; 4]>();
-        let data = unsafe { from_raw_parts(ptr, area as _) };
-        data
+        let data = unsafe { from_raw_parts(ptr, area) };
+        Ok(data)
     }
 }
 
+/// A [`Renderer`] bundled with its state behind a trait object, so the renderer can be picked at runtime without
+/// making everything that drives it generic
+pub trait DynRenderer {
+    fn width(&self) -> u32;
+    fn height(&self) -> u32;
+    /// Checks the frame against the renderer's dimensions and consumes it
+    fn consume_frame(&mut self, data: &[u8]) -> Result<(), RenderError>;
+    /// See [`Renderer::invalidate`]
+    fn invalidate(&mut self);
+    fn render_frame(&mut self, output: &mut dyn Write) -> io::Result<()>;
+}
+
+struct WithState<R: Renderer> {
+    renderer: R,
+    state: R::State,
+}
+
+impl<R: Renderer> DynRenderer for WithState<R> {
+    fn width(&self) -> u32 {
+        self.renderer.width()
+    }
+    fn height(&self) -> u32 {
+        self.renderer.height()
+    }
+    fn consume_frame(&mut self, data: &[u8]) -> Result<(), RenderError> {
+        self.renderer.consume_frame(data)
+    }
+    fn invalidate(&mut self) {
+        self.renderer.invalidate(&mut self.state)
+    }
+    fn render_frame(&mut self, mut output: &mut dyn Write) -> io::Result<()> {
+        self.renderer.render_frame(&mut output, &mut self.state)
+    }
+}
+
+/// Makes a boxed renderer for frames of the given size
+pub type RendererFactory = fn(u32, u32, &RenderOptions) -> Box<dyn DynRenderer>;
+
+/// The [`RendererFactory`] for `R`
+pub fn boxed<R: Renderer + 'static>(
+    width: u32,
+    height: u32,
+    options: &RenderOptions,
+) -> Box<dyn DynRenderer> {
+    let renderer = R::from_dims(width, height, options);
+    let state = renderer.create_state();
+    Box::new(WithState { renderer, state })
+}
+
 pub struct DefaultRenderer<C: Colorize> {
     width: u32,
     height: u32,
@@ -40,11 +419,32 @@ pub struct DefaultRenderer<C: Colorize> {
     // [r, g, b, char]
     color_buf: Box<[Pixel]>,
     prev_buf: Box<[Pixel]>,
+    adjust_lut: ChannelLut,
+    color_lut: ChannelLut,
+    use_rep: bool,
+    row_offset: u32,
+    col_offset: u32,
+    dither: Dither,
+    // scratch space for error diffusion
+    errors: Vec<[i16; 3]>,
+    temporal_dither: bool,
+    // flipped every frame, so the temporal dithering alternates
+    odd_frame: bool,
+    edges: Edges,
+    // scratch space for the luminance that the edges are found in
+    lums: Vec<u8>,
+    luma_weights: LumaWeights,
+    black_threshold: u8,
+    color_tolerance: u8,
+    motion_threshold: u8,
+    palette: Option<Arc<Palette>>,
+    // whether `prev_buf` holds a frame yet, rather than the zeros it starts with
+    has_prev: bool,
     _phantom: PhantomData<C>,
 }
 
 impl<C: Colorize> DefaultRenderer<C> {
-    pub fn new(width: u32, height: u32) -> Self {
+    pub fn new(width: u32, height: u32, options: &RenderOptions, default_gamma: f32) -> Self {
         let num_pixels = width * height;
         let color_buf = vec![[0u8, 0, 0, 0]; num_pixels as usize].into_boxed_slice();
 
@@ -54,18 +454,103 @@ impl<C: Colorize> DefaultRenderer<C> {
 
             prev_buf: color_buf.clone(),
             color_buf,
+            adjust_lut: options.adjust_lut(),
+            color_lut: options.color_lut(default_gamma),
+            use_rep: options.use_rep,
+            row_offset: options.row_offset,
+            col_offset: options.col_offset,
+            dither: options.dither,
+            errors: Vec::new(),
+            temporal_dither: options.temporal_dither,
+            odd_frame: false,
+            edges: options.edges,
+            lums: Vec::new(),
+            luma_weights: options.luma_weights,
+            black_threshold: options.black_threshold,
+            color_tolerance: options.color_tolerance,
+            motion_threshold: options.motion_threshold,
+            palette: options.palette.clone(),
+            has_prev: false,
             _phantom: PhantomData,
         }
     }
+    /// Replaces the luminance that `consume` left in place of each character with the character for the strength of
+    /// the edge there, found with a Sobel filter
+    fn detect_edges(&mut self) {
+        self.lums.clear();
+        self.lums
+            .extend(self.color_buf.iter().map(|pixel| pixel[3]));
+        let mono = self.edges == Edges::Mono;
+        sobel(&self.lums, self.width as usize, |i, magnitude| {
+            let pixel = &mut self.color_buf[i];
+            if mono {
+                *pixel = [u8::MAX; 4];
+            }
+            pixel[3] = if self.lums[i] < self.black_threshold {
+                b' '
+            } else {
+                ASCII_CHARS.as_bytes()[(magnitude >> 2) as usize]
+            };
+        });
+    }
+    /// Gives every space the same black color after the dithering, so runs of them merge into one stride and stay
+    /// the same between frames. The color of a space never shows, so this doesn't change what is drawn
+    fn blank_spaces(&mut self) {
+        for pixel in self.color_buf.iter_mut() {
+            if pixel[3] == b' ' {
+                *pixel = [0, 0, 0, b' '];
+            }
+        }
+    }
+    /// Puts back the pixels from the last frame whose character stayed the same and whose color moved by at most
+    /// `motion_threshold` in every channel, so they aren't redrawn. Since the last frame is what's on the screen, a
+    /// slow fade still gets drawn once it has moved far enough, instead of creeping by a little every frame
+    fn hold_still(&mut self) {
+        let threshold = self.motion_threshold;
+        if threshold > 0 && self.has_prev {
+            for (pixel, prev) in self.color_buf.iter_mut().zip(self.prev_buf.iter()) {
+                if pixel[3] == prev[3] && (0..3).all(|i| pixel[i].abs_diff(prev[i]) <= threshold) {
+                    *pixel = *prev;
+                }
+            }
+        }
+        self.has_prev = true;
+    }
+    /// Dithers the colors in place, after the adjustments and before they are quantized
+    fn dither(&mut self) {
+        match self.dither {
+            Dither::None => (),
+            Dither::Bayer => dither_bayer(&mut self.color_buf, self.width as usize, C::STEP),
+            Dither::Fs => dither_floyd_steinberg::<C>(
+                &mut self.color_buf,
+                self.width as usize,
+                &mut self.errors,
+                self.palette.as_deref(),
+            ),
+        }
+        if self.temporal_dither {
+            dither_temporal(
+                &mut self.color_buf,
+                self.width as usize,
+                C::STEP,
+                self.odd_frame,
+            );
+            self.odd_frame = !self.odd_frame;
+        }
+    }
 }
 
+// the gammas used when `RenderOptions::gamma` is unset
+const FG_GAMMA: f32 = 0.5;
+const BG_GAMMA: f32 = 1.;
+
 macro_rules! impl_fg {
     ([$($ty:ty),*]) => {
         $(impl Renderer for DefaultRenderer<$ty> {
             type State = Differ<$ty>;
-            fn from_dims(width: u32, height: u32) -> Self { Self::new(width, height) }
+            fn from_dims(width: u32, height: u32, options: &RenderOptions) -> Self { Self::new(width, height, options, FG_GAMMA) }
             fn create_state(&self) -> Self::State {
-                Differ::new(self.width, self.height)
+                Differ::new(self.width, self.height).with_palette(self.palette.clone())
             }
             fn width(&self) -> u32 {
                 self.width
@@ -74,14 +559,39 @@ macro_rules! impl_fg {
                 self.height
             }
             fn consume(&mut self, data: &[Pixel]) {
-                 std::mem::swap(&mut self.color_buf, &mut self.prev_buf);
-                for (i, pixel) in data.iter().enumerate() {
-                    let lum = luminance(*pixel);
-                    let index = lum >> 2;
-                    let mut pixel = pixel.clone();
-                    pixel[3] = ASCII_CHARS.as_bytes()[index as usize];
-                    self.color_buf[i] = gamma_correct(pixel);
+                std::mem::swap(&mut self.color_buf, &mut self.prev_buf);
+                let (adjust_lut, color_lut) = (&self.adjust_lut, &self.color_lut);
+                let edges = self.edges != Edges::Off;
+                let (luma_weights, black_threshold) = (self.luma_weights, self.black_threshold);
+                map_chunks(&mut self.color_buf, data, |dst, src| {
+                    // the tail of the last chunk is padding, and its luminance is thrown away
+                    let mut adjusted = [[0; 4]; LUMINANCE_CHUNK];
+                    for (adjusted, pixel) in adjusted.iter_mut().zip(src) {
+                        *adjusted = adjust_lut.apply(*pixel);
+                    }
+                    let lums = luminance_chunk(&adjusted, luma_weights);
+                    for ((dst, pixel), lum) in dst.iter_mut().zip(src).zip(lums) {
+                        let mut pixel = color_lut.apply(*pixel);
+                        // the edges need the luminance of the neighbors, so it's kept until they're all done
+                        pixel[3] = if edges {
+                            lum
+                        } else {
+                            ascii_char(lum, black_threshold)
+                        };
+                        *dst = pixel;
+                    }
+                });
+                if edges {
+                    self.detect_edges();
                 }
+                self.dither();
+                if self.black_threshold > 0 {
+                    self.blank_spaces();
+                }
+                self.hold_still();
+            }
+            fn invalidate(&mut self, state: &mut Self::State) {
+                state.force_full_redraw();
             }
             fn render_frame(
                 &self,
@@ -90,25 +600,12 @@ macro_rules! impl_fg {
             ) -> io::Result<()> {
 
                 // profiling suggests that we are almost 100% io-bound, so we are basically free to do any optimization on escape sequences
-                state.assign_diff(&self.color_buf, &self.prev_buf);
-
-                let mut prev_end: usize = 0;
-                let mut prev_color = <$ty>::default();
-
-                for (i, (pos, color, chr)) in state.data().iter().enumerate() {
-                    render_stride(
-                        i,
-                        pos,
-                        color,
-                        chr,
-                        &mut prev_end,
-                        &mut prev_color,
-                        output,
-                        self.width,
-                    )?;
-                }
+                state.assign_diff_within(&self.color_buf, &self.prev_buf, self.color_tolerance);
 
-                output.flush()?;
+                let mut strides = StrideWriter::new(self.width, (self.row_offset, self.col_offset), self.use_rep);
+                for (pos, color, chr) in state.data() {
+                    strides.write(pos, color, &[*chr], output)?;
+                }
                 Ok(())
             }
         })+
@@ -119,9 +616,9 @@ macro_rules! impl_bg {
     ([$($ty:ty),*]) => {
         $(impl Renderer for DefaultRenderer<$ty> {
             type State = Differ<$ty>;
-            fn from_dims(width: u32, height: u32) -> Self { Self::new(width, height) }
+            fn from_dims(width: u32, height: u32, options: &RenderOptions) -> Self { Self::new(width, height, options, BG_GAMMA) }
             fn create_state(&self) -> Self::State {
-                Differ::new(self.width, self.height)
+                Differ::new(self.width, self.height).with_palette(self.palette.clone())
             }
             fn width(&self) -> u32 {
                 self.width
@@ -131,8 +628,18 @@ macro_rules! impl_bg {
             }
             fn consume(&mut self, data: &[Pixel]) {
                 std::mem::swap(&mut self.color_buf, &mut self.prev_buf);
-                // apply no filters. just a memcpy
-                self.color_buf.copy_from_slice(data)
+                if self.color_lut.is_identity() {
+                    // apply no filters. just a memcpy
+                    self.color_buf.copy_from_slice(data)
+                } else {
+                    let color_lut = &self.color_lut;
+                    map_pixels(&mut self.color_buf, data, |pixel| color_lut.apply(pixel));
+                }
+                self.dither();
+                self.hold_still();
+            }
+            fn invalidate(&mut self, state: &mut Self::State) {
+                state.force_full_redraw();
             }
             fn render_frame(
                 &self,
@@ -142,33 +649,35 @@ macro_rules! impl_bg {
 
 
                 // profiling suggests that we are almost 100% io-bound, so we are basically free to do any optimization on escape sequences
-                state.assign_diff(&self.color_buf, &self.prev_buf);
-
-                let mut prev_end: usize = 0;
-                let mut prev_color = <$ty>::default();
-
-                 for (i, (pos, color, _)) in state.data().iter().enumerate() {
-                    render_stride(
-                        i,
-                        pos,
-                        color,
-                        &b' ',
-                        &mut prev_end,
-                        &mut prev_color,
-                        output,
-                        self.width,
-                    )?;
-                }
+                state.assign_diff_within(&self.color_buf, &self.prev_buf, self.color_tolerance);
 
-                output.flush()?;
+                let mut strides = StrideWriter::new(self.width, (self.row_offset, self.col_offset), self.use_rep);
+                for (pos, color, _) in state.data() {
+                    strides.write(pos, color, b" ", output)?;
+                }
                 Ok(())
             }
         })+
     };
 }
 
-impl_fg!([Ansi256, Rgb]);
-impl_bg!([BackgroundAnsi256, BackgroundRgb]);
+impl_fg!([
+    Ansi16,
+    Ansi256,
+    Rgb,
+    Grayscale,
+    Grayscale<Ansi16>,
+    Grayscale<Ansi256>,
+    NoColor
+]);
+impl_bg!([
+    BackgroundAnsi16,
+    BackgroundAnsi256,
+    BackgroundRgb,
+    BackgroundGrayscale,
+    Grayscale<BackgroundAnsi16>,
+    Grayscale<BackgroundAnsi256>
+]);
 
 // original 70 character gradient
 // const ASCII_CHARS: &str =
@@ -178,9 +687,126 @@ impl_bg!([BackgroundAnsi256, BackgroundRgb]);
 // const ASCII_CHARS: &str = "$@B%8&W#*oahkbdpqwmZOQCJUYXzcvuxrjft/\\|()1{}[]?-_+~<>i!lI;:,\"`. ";
 const ASCII_CHARS: &str = " .`\",:;Il!i><~+_-?][}{1)(|\\/tfjrxuvczXYUJCQOZmwqpdbkhao*#W&8%B@$";
 
-const fn luminance(pixel: [u8; 4]) -> u8 {
-    let [r, g, b, _] = pixel;
-    (((r as u32) * 3 + (b as u32) + ((g as u32) << 2)) >> 3) as u8
+/// The character for luminance `lum`, or a space if it's under `black_threshold`
+fn ascii_char(lum: u8, black_threshold: u8) -> u8 {
+    if lum < black_threshold {
+        b' '
+    } else {
+        ASCII_CHARS.as_bytes()[(lum >> 2) as usize]
+    }
+}
+
+/// Draws the characters without color like `DefaultRenderer<NoColor>`, but from frames in `I420` or `NV12`, whose
+/// first plane is already the luminance. This skips converting the frames to RGB at all.
+///
+/// Frames in RGBx still work through [`Renderer::consume`], for callers that don't negotiate the format
+pub struct LumaRenderer {
+    width: u32,
+    height: u32,
+    // [0, 0, 0, char], so the buffers can go through a `Differ<NoColor>` like the other renderers
+    color_buf: Box<[Pixel]>,
+    prev_buf: Box<[Pixel]>,
+    adjust_lut: ChannelLut,
+    use_rep: bool,
+    row_offset: u32,
+    col_offset: u32,
+    black_threshold: u8,
+}
+
+impl LumaRenderer {
+    /// The distance between the rows of the luminance plane, which gstreamer pads to a multiple of 4 bytes
+    fn stride(&self) -> usize {
+        (self.width as usize).next_multiple_of(4)
+    }
+    /// The sizes of an `I420` and an `NV12` frame at the renderer's dimensions, with gstreamer's default strides.
+    /// Both formats have the chroma at half the resolution, but `NV12` interleaves its two planes into one
+    fn frame_sizes(&self) -> [usize; 2] {
+        let rows = (self.height as usize).next_multiple_of(2);
+        let luma = self.stride() * rows;
+        let chroma_stride = (self.width as usize)
+            .next_multiple_of(2)
+            .div_ceil(2)
+            .next_multiple_of(4);
+        [
+            luma + 2 * chroma_stride * (rows / 2),
+            luma + self.stride() * (rows / 2),
+        ]
+    }
+}
+
+impl Renderer for LumaRenderer {
+    type State = Differ<NoColor>;
+    fn from_dims(width: u32, height: u32, options: &RenderOptions) -> Self {
+        let color_buf = vec![[0u8, 0, 0, 0]; width as usize * height as usize].into_boxed_slice();
+        Self {
+            width,
+            height,
+            prev_buf: color_buf.clone(),
+            color_buf,
+            adjust_lut: options.adjust_lut(),
+            use_rep: options.use_rep,
+            row_offset: options.row_offset,
+            col_offset: options.col_offset,
+            black_threshold: options.black_threshold,
+        }
+    }
+    fn create_state(&self) -> Self::State {
+        Differ::new(self.width, self.height)
+    }
+    fn width(&self) -> u32 {
+        self.width
+    }
+    fn height(&self) -> u32 {
+        self.height
+    }
+    fn consume(&mut self, data: &[Pixel]) {
+        std::mem::swap(&mut self.color_buf, &mut self.prev_buf);
+        let (adjust_lut, black_threshold) = (&self.adjust_lut, self.black_threshold);
+        map_pixels(&mut self.color_buf, data, |pixel| {
+            let [r, g, b, _] = adjust_lut.apply(pixel);
+            [0, 0, 0, ascii_char(luminance([r, g, b]), black_threshold)]
+        });
+    }
+    fn consume_frame(&mut self, data: &[u8]) -> Result<(), RenderError> {
+        let sizes = self.frame_sizes();
+        if !sizes.contains(&data.len()) {
+            // an RGBx frame is fine too, and otherwise this reports the size of an I420 frame
+            return match self.verify_input(data) {
+                Ok(pixels) => {
+                    self.consume(pixels);
+                    Ok(())
+                }
+                Err(_) => Err(RenderError::FrameSize {
+                    expected: sizes[0],
+                    got: data.len(),
+                }),
+            };
+        }
+        std::mem::swap(&mut self.color_buf, &mut self.prev_buf);
+        let (width, stride, black_threshold) =
+            (self.width as usize, self.stride(), self.black_threshold);
+        for (cells, row) in self.color_buf.chunks_mut(width).zip(data.chunks(stride)) {
+            for (cell, y) in cells.iter_mut().zip(row) {
+                // the lut works on whole pixels, and a gray pixel is the same in every channel
+                let lum = self.adjust_lut.apply([*y, *y, *y, 0])[0];
+                *cell = [0, 0, 0, ascii_char(lum, black_threshold)];
+            }
+        }
+        Ok(())
+    }
+    fn invalidate(&mut self, state: &mut Self::State) {
+        state.force_full_redraw();
+    }
+    fn render_frame(&self, output: &mut impl Write, state: &mut Self::State) -> io::Result<()> {
+        state.assign_diff(&self.color_buf, &self.prev_buf);
+
+        let mut strides =
+            StrideWriter::new(self.width, (self.row_offset, self.col_offset), self.use_rep);
+        for (pos, color, chr) in state.data() {
+            strides.write(pos, color, &[*chr], output)?;
+        }
+        Ok(())
+    }
 }
 
 // #[allow(clippy::cast_possible_truncation)]
@@ -198,74 +824,760 @@ const fn luminance(pixel: [u8; 4]) -> u8 {
 //     [r, g, b, ch]
 // }
 #[allow(clippy::cast_possible_truncation)]
-fn gamma_correct(pixel: Pixel) -> Pixel {
-    let [r, g, b, c] = pixel;
-    const GAMMA: f32 = 0.5;
-    let r = r as f32 / 255.;
-    let g = g as f32 / 255.;
-    let b = b as f32 / 255.;
-    let r = (r.powf(GAMMA) * 255.).min(u8::MAX as _) as u8;
-    let g = (g.powf(GAMMA) * 255.).min(u8::MAX as _) as u8;
-    let b = (b.powf(GAMMA) * 255.).min(u8::MAX as _) as u8;
-    [r, g, b, c]
-}
-fn render_stride<C: Colorize>(
-    i: usize,
-    pos: &Range<usize>,
-    color: &C,
-    chr: &u8,
-    prev_end: &mut usize,
-    prev_color: &mut C,
-    mut output: &mut impl Write,
+fn gamma_correct(x: u8, gamma: f32) -> u8 {
+    let x = x as f32 / 255.;
+    (x.powf(gamma) * 255.).min(u8::MAX as _) as u8
+}
+/// The upper half block, which gets the top pixel as its foreground and the bottom pixel as its background
+const HALF_BLOCK: &[u8] = "\u{2580}".as_bytes();
+
+/// Renders two pixels per cell with the upper half block, doubling the vertical resolution
+pub struct HalfBlockRenderer {
+    width: u32,
+    // in pixels, which is twice the number of rows
+    height: u32,
+    cells: Box<[HalfBlock]>,
+    prev_cells: Box<[HalfBlock]>,
+    color_lut: ChannelLut,
+    use_rep: bool,
+    row_offset: u32,
+    col_offset: u32,
+}
+
+impl Renderer for HalfBlockRenderer {
+    type State = Differ<HalfBlock>;
+    fn from_dims(width: u32, height: u32, options: &RenderOptions) -> Self {
+        // an odd last row of pixels gets a cell to itself
+        let rows = height.div_ceil(2);
+        let cells = vec![HalfBlock::default(); width as usize * rows as usize].into_boxed_slice();
+        Self {
+            width,
+            height,
+            prev_cells: cells.clone(),
+            cells,
+            color_lut: options.color_lut(BG_GAMMA),
+            use_rep: options.use_rep,
+            row_offset: options.row_offset,
+            col_offset: options.col_offset,
+        }
+    }
+    fn create_state(&self) -> Self::State {
+        Differ::new(self.width, self.height.div_ceil(2))
+    }
+    fn width(&self) -> u32 {
+        self.width
+    }
+    fn height(&self) -> u32 {
+        self.height
+    }
+    fn consume(&mut self, data: &[Pixel]) {
+        std::mem::swap(&mut self.cells, &mut self.prev_cells);
+        let width = self.width as usize;
+        let rgb = |pixel: Pixel| {
+            let [r, g, b, _] = self.color_lut.apply(pixel);
+            [r, g, b]
+        };
+        for (cells, pixels) in self.cells.chunks_mut(width).zip(data.chunks(width * 2)) {
+            let (top, bottom) = pixels.split_at(width.min(pixels.len()));
+            // the bottom half of an odd last row is left the same color as the top
+            let bottom = if bottom.is_empty() { top } else { bottom };
+            for ((cell, top), bottom) in cells.iter_mut().zip(top).zip(bottom) {
+                *cell = HalfBlock::new(rgb(*top), rgb(*bottom));
+            }
+        }
+    }
+    fn invalidate(&mut self, state: &mut Self::State) {
+        state.force_full_redraw();
+    }
+    fn render_frame(&self, output: &mut impl Write, state: &mut Self::State) -> io::Result<()> {
+        state.assign_colors(&self.cells, &self.prev_cells);
+
+        let mut strides =
+            StrideWriter::new(self.width, (self.row_offset, self.col_offset), self.use_rep);
+        for (pos, color, _) in state.data() {
+            strides.write(pos, color, HALF_BLOCK, output)?;
+        }
+        Ok(())
+    }
+}
+
+/// The quadrant blocks for each pattern of 2x2 pixels, where bit 0 is the top left pixel, bit 1 the top right, bit 2
+/// the bottom left, and bit 3 the bottom right. A set bit is drawn in the foreground color
+const QUADRANTS: [&str; 16] = [
+    " ", "\u{2598}", "\u{259D}", "\u{2580}", "\u{2596}", "\u{258C}", "\u{259E}", "\u{259B}",
+    "\u{2597}", "\u{259A}", "\u{2590}", "\u{259C}", "\u{2584}", "\u{2599}", "\u{259F}", "\u{2588}",
+];
+
+/// Picks the pattern that splits `pixels` into the two groups that are closest to their own average color, and
+/// returns it with the averages of the set and unset pixels
+fn split_colors(pixels: &[[u8; 3]]) -> (usize, [u8; 3], [u8; 3]) {
+    let sum = |mask: usize, set: bool| {
+        let (mut total, mut count) = ([0u32; 3], 0u32);
+        for (i, pixel) in pixels.iter().enumerate() {
+            if (mask >> i & 1 == 1) == set {
+                for (total, channel) in total.iter_mut().zip(pixel) {
+                    *total += *channel as u32;
+                }
+                count += 1;
+            }
+        }
+        (total, count)
+    };
+    // the squared error of a group is the sum of its squares minus the square of its sum over its size, and the sum
+    // of squares is the same for every split, so the best split has the biggest sum of those squares over the sizes.
+    // they are scaled by a multiple of every size to stay in integers
+    let scale = (1..=pixels.len() as u64).product::<u64>();
+    let score = |(total, count): ([u32; 3], u32)| match count {
+        0 => 0,
+        count => total.iter().map(|t| (*t as u64).pow(2)).sum::<u64>() * scale / count as u64,
+    };
+    let average =
+        |(total, count): ([u32; 3], u32)| total.map(|t| ((t + count / 2) / count.max(1)) as u8);
+    // swapping the colors gives the same split, so only the patterns with the last pixel unset need to be tried.
+    // they are tried backwards so ties go to the simplest pattern, which makes a flat cell a space
+    let (mask, _) = (0..1 << (pixels.len() - 1))
+        .rev()
+        .map(|mask| (mask, score(sum(mask, true)) + score(sum(mask, false))))
+        .max_by_key(|(_, score)| *score)
+        .unwrap();
+    let background = average(sum(mask, false));
+    let foreground = match mask {
+        // one color for the whole cell
+        0 => background,
+        mask => average(sum(mask, true)),
+    };
+    (mask, foreground, background)
+}
+
+/// The bytes of the sextant blocks for each pattern of 2x3 pixels and how many of them there are, with the bits in the
+/// same order as [`QUADRANTS`]. The patterns that already have a block in the older block elements, the empty and full
+/// cell and the left and right half, are left out of the sextant range
+const SEXTANTS: [([u8; 4], usize); 64] = {
+    let mut sextants = [([0; 4], 0); 64];
+    let mut mask = 0;
+    while mask < 64 {
+        let c = match mask {
+            0 => ' ',
+            21 => '\u{258C}',
+            42 => '\u{2590}',
+            63 => '\u{2588}',
+            // the gaps for the left and right half shift the ones after them down
+            mask => {
+                match char::from_u32(0x1FB00 + mask - 1 - (mask > 21) as u32 - (mask > 42) as u32) {
+                    Some(c) => c,
+                    None => unreachable!(),
+                }
+            }
+        };
+        let mut bytes = [0; 4];
+        let len = c.encode_utf8(&mut bytes).len();
+        sextants[mask as usize] = (bytes, len);
+        mask += 1;
+    }
+    sextants
+};
+
+/// A set of block characters that each split a cell into a grid of pixels
+pub trait Blocks {
+    const COLUMNS: usize;
+    const ROWS: usize;
+    /// The block with the pixels in `mask` set, where bit 0 is the top left pixel and the bits go across each row
+    fn glyph(mask: u8) -> &'static [u8];
+}
+
+/// The quadrant blocks, which split a cell into 2x2 pixels
+pub struct Quadrants;
+
+impl Blocks for Quadrants {
+    const COLUMNS: usize = 2;
+    const ROWS: usize = 2;
+    fn glyph(mask: u8) -> &'static [u8] {
+        QUADRANTS[mask as usize].as_bytes()
+    }
+}
+
+/// The sextant blocks from the Symbols for Legacy Computing block, which split a cell into 2x3 pixels.
+/// Not every font has them
+pub struct Sextants;
+
+impl Blocks for Sextants {
+    const COLUMNS: usize = 2;
+    const ROWS: usize = 3;
+    fn glyph(mask: u8) -> &'static [u8] {
+        let (bytes, len) = &SEXTANTS[mask as usize];
+        &bytes[..*len]
+    }
+}
+
+/// Renders a grid of pixels per cell with the block characters in `B`, which multiplies the resolution but only has
+/// two colors per cell
+pub struct BlockRenderer<B: Blocks> {
+    // in pixels, which is a multiple of the number of columns and rows
     width: u32,
+    height: u32,
+    // the colors of each cell and the mask of its block
+    cells: Box<[(HalfBlock, u8)]>,
+    prev_cells: Box<[(HalfBlock, u8)]>,
+    color_lut: ChannelLut,
+    use_rep: bool,
+    row_offset: u32,
+    col_offset: u32,
+    _phantom: PhantomData<B>,
+}
+
+/// Draws 2x2 pixels per cell
+pub type QuadrantRenderer = BlockRenderer<Quadrants>;
+/// Draws 2x3 pixels per cell
+pub type SextantRenderer = BlockRenderer<Sextants>;
+
+impl<B: Blocks> BlockRenderer<B> {
+    /// The number of columns and rows of cells
+    fn cells(&self) -> (usize, usize) {
+        (
+            (self.width as usize).div_ceil(B::COLUMNS),
+            (self.height as usize).div_ceil(B::ROWS),
+        )
+    }
+}
+
+impl<B: Blocks> Renderer for BlockRenderer<B> {
+    type State = Differ<HalfBlock>;
+    fn from_dims(width: u32, height: u32, options: &RenderOptions) -> Self {
+        // a partial last row or column of pixels gets cells to itself
+        let cells = (width as usize).div_ceil(B::COLUMNS) * (height as usize).div_ceil(B::ROWS);
+        let cells = vec![(HalfBlock::default(), 0); cells].into_boxed_slice();
+        Self {
+            width,
+            height,
+            prev_cells: cells.clone(),
+            cells,
+            color_lut: options.color_lut(BG_GAMMA),
+            use_rep: options.use_rep,
+            row_offset: options.row_offset,
+            col_offset: options.col_offset,
+            _phantom: PhantomData,
+        }
+    }
+    fn create_state(&self) -> Self::State {
+        let (columns, rows) = self.cells();
+        Differ::new(columns as u32, rows as u32)
+    }
+    fn width(&self) -> u32 {
+        self.width
+    }
+    fn height(&self) -> u32 {
+        self.height
+    }
+    fn consume(&mut self, data: &[Pixel]) {
+        std::mem::swap(&mut self.cells, &mut self.prev_cells);
+        let (width, height) = (self.width as usize, self.height as usize);
+        let (columns, _) = self.cells();
+        let mut pixels = [[0; 3]; 6];
+        let pixels = &mut pixels[..B::COLUMNS * B::ROWS];
+        for (i, cell) in self.cells.iter_mut().enumerate() {
+            let (left, top) = (i % columns * B::COLUMNS, i / columns * B::ROWS);
+            for (j, pixel) in pixels.iter_mut().enumerate() {
+                // the pixels past a partial last row or column are copies of the ones on the edge
+                let x = (left + j % B::COLUMNS).min(width - 1);
+                let y = (top + j / B::COLUMNS).min(height - 1);
+                let [r, g, b, _] = self.color_lut.apply(data[y * width + x]);
+                *pixel = [r, g, b];
+            }
+            let (mask, foreground, background) = split_colors(pixels);
+            *cell = (HalfBlock::new(foreground, background), mask as u8);
+        }
+    }
+    fn invalidate(&mut self, state: &mut Self::State) {
+        state.force_full_redraw();
+    }
+    fn render_frame(&self, output: &mut impl Write, state: &mut Self::State) -> io::Result<()> {
+        state.assign_glyphs(&self.cells, &self.prev_cells);
+
+        let (columns, _) = self.cells();
+        let mut strides = StrideWriter::new(
+            columns as u32,
+            (self.row_offset, self.col_offset),
+            self.use_rep,
+        );
+        for (pos, color, glyph) in state.data() {
+            strides.write(pos, color, B::glyph(*glyph), output)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the strides of a single frame, keeping track of where the cursor was left and which color is active
+struct StrideWriter<C: Colorize> {
+    width: usize,
+    // the rows and columns before the video
+    offset: (usize, usize),
+    use_rep: bool,
+    prev_end: usize,
+    prev_color: Option<C>,
+}
+
+impl<C: Colorize> StrideWriter<C> {
+    fn new(width: u32, (row_offset, col_offset): (u32, u32), use_rep: bool) -> Self {
+        Self {
+            width: width as usize,
+            offset: (row_offset as usize, col_offset as usize),
+            use_rep,
+            prev_end: 0,
+            prev_color: None,
+        }
+    }
+    fn write(
+        &mut self,
+        pos: &Range<usize>,
+        color: &C,
+        glyph: &[u8],
+        mut output: &mut impl Write,
+    ) -> io::Result<()> {
+        // If the previous end is the same as the start, that means the cursor is in the right position
+        // and therefore we do not need to print the escape to skip to the line,
+        // unless the requred position *is* the origin or the start of a row.
+        // In that case, the cursor is at the end of the row before it, or who knows where for the origin.
+        if pos.start != self.prev_end || self.prev_end.is_multiple_of(self.width) {
+            move_cursor(self.prev_end, pos.start, self.width, self.offset, output)?;
+        }
+        if self.prev_color.as_ref() != Some(color) {
+            color.write_escape(&mut output)?;
+        }
+
+        // write the stride one line at a time
+        let mut start = pos.start;
+        while start < pos.end {
+            let end = pos.end.min((start / self.width + 1) * self.width);
+            if start != pos.start {
+                // a newline would only land on the first column of the video when the video starts on the first
+                // column of the terminal, and only when the terminal turns it into a carriage return too, which a
+                // pty in raw mode or a file played back somewhere else doesn't
+                let (row_offset, col_offset) = self.offset;
+                let line = start / self.width + 1 + row_offset;
+                write!(output, "\x1b[{line};{}H", col_offset + 1)?;
+            }
+            self.write_run(glyph, end - start, output)?;
+            start = end;
+        }
+        self.prev_end = pos.end;
+        self.prev_color = Some(color.clone());
+        Ok(())
+    }
+    /// Writes `glyph` `len` times, either with the REP escape or in bulk
+    fn write_run(&self, glyph: &[u8], len: usize, output: &mut impl Write) -> io::Result<()> {
+        // REP repeats the preceding character, so it only pays off once the count is shorter than the run
+        if self.use_rep && 3 + digits(len - 1) < len - 1 {
+            output.write_all(glyph)?;
+            return write!(output, "\x1b[{}b", len - 1);
+        }
+        let &[chr] = glyph else {
+            // multibyte glyphs are rare enough that they don't get the bulk path
+            for _ in 0..len {
+                output.write_all(glyph)?;
+            }
+            return Ok(());
+        };
+        let buf = [chr; 64];
+        let mut left = len;
+        while left > 0 {
+            let n = left.min(buf.len());
+            output.write_all(&buf[..n])?;
+            left -= n;
+        }
+        Ok(())
+    }
+}
+
+/// Moves the cursor from the end of the previous stride to `start`,
+/// picking whichever of absolute and relative motion takes fewer bytes.
+fn move_cursor(
+    prev_end: usize,
+    start: usize,
+    width: usize,
+    (row_offset, col_offset): (usize, usize),
+    output: &mut impl Write,
 ) -> io::Result<()> {
-    // If the previous end is the same as the start, that means the cursor is in the right position
-    // and therefore we do not need to print the escape to skip to the line,
-    // unless the requred position *is* the origin.
-    // In that case, we almost always need to jump to it.
-    if &pos.start != prev_end || prev_end == &0 {
-        let line = pos.start / width as usize;
-        let column = pos.start % width as usize;
-        // it is almost always less characters to skip directly to the line and column than to use relative motion
-        // maybe i'll optimize that too
-        write!(output, "\x1b[{};{}H", line, column)?;
-    }
-    if color != prev_color || i == 0 {
-        color.write_escape(&mut output)?;
-    }
-
-    let mut is_first = true;
-    for i in pos.clone() {
-        let col = i % width as usize;
-        if col == 0 && !is_first {
-            output.write_all(b"\n")?;
-        }
-        output.write_all(&[*chr])?;
-        is_first = false;
-    }
-    *prev_end = pos.end;
-    *prev_color = color.clone();
-    Ok(())
+    // escape sequences are one-indexed
+    let line = start / width + 1 + row_offset;
+    let column = start % width + 1 + col_offset;
+    let absolute_len = 4 + digits(line) + digits(column);
+
+    // When the previous stride ended in the last column (or nothing was drawn yet),
+    // the terminal is waiting to wrap and the cursor's column isn't reliable.
+    if !prev_end.is_multiple_of(width) && start > prev_end {
+        let (prev_line, prev_column) = (
+            prev_end / width + 1 + row_offset,
+            prev_end % width + 1 + col_offset,
+        );
+        let down = line - prev_line;
+        let (right, left) = (
+            column.saturating_sub(prev_column),
+            prev_column.saturating_sub(column),
+        );
+        let relative_len = motion_len(down) + motion_len(right) + motion_len(left);
+        if relative_len < absolute_len {
+            write_motion(output, down, b'B')?;
+            write_motion(output, right, b'C')?;
+            return write_motion(output, left, b'D');
+        }
+    }
+    write!(output, "\x1b[{line};{column}H")
+}
+
+/// The length of a relative cursor motion escape of `n` cells, where 1 cell can omit the count.
+const fn motion_len(n: usize) -> usize {
+    match n {
+        0 => 0,
+        1 => 3,
+        n => 3 + digits(n),
+    }
+}
+
+fn write_motion(output: &mut impl Write, n: usize, direction: u8) -> io::Result<()> {
+    match n {
+        0 => Ok(()),
+        1 => output.write_all(&[b'\x1b', b'[', direction]),
+        n => write!(output, "\x1b[{n}{}", direction as char),
+    }
+}
+
+const fn digits(mut n: usize) -> usize {
+    let mut len = 1;
+    while n >= 10 {
+        n /= 10;
+        len += 1;
+    }
+    len
+}
+
+/// The biggest chunk the kitty protocol asks terminals to accept
+pub const KITTY_CHUNK: usize = 4096;
+
+/// The id every frame is transmitted to, so each one replaces the last instead of piling up
+const KITTY_IMAGE_ID: u32 = 1;
+
+/// What the next frame has to send to the terminal
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum KittyUpdate {
+    /// The frame is the same as the last one
+    Unchanged,
+    /// The whole image, which is needed for the first frame
+    Full,
+    /// Only the rectangle with the pixels that changed, as `(x, y, width, height)`
+    Region(Rect),
 }
 
 pub struct KittyRenderer {
     width: u32,
     height: u32,
-    encoded: String,
+    // the size changes with every frame when compressing
+    encoded: Vec<u8>,
+    color_lut: ChannelLut,
+    // only used when `color_lut` isn't the identity
+    adjusted: Vec<Pixel>,
+    row_offset: u32,
+    col_offset: u32,
+    compress: bool,
+    compressed: Vec<u8>,
+    chunk: usize,
+    // the last frame, to find what changed. empty before the first frame
+    prev: Vec<Pixel>,
+    // the pixels in the changed region, packed together
+    region: Vec<Pixel>,
+    update: KittyUpdate,
 }
 
 impl Renderer for KittyRenderer {
     type State = ();
-    fn from_dims(width: u32, height: u32) -> Self {
-        let len = width as usize * height as usize * 4;
-        fn ceiling_div(x: usize, y: usize) -> usize {
-            (x + y - 1) / y
+    fn from_dims(width: u32, height: u32, options: &RenderOptions) -> Self {
+        Self {
+            width,
+            height,
+            encoded: Vec::new(),
+            color_lut: options.color_lut(BG_GAMMA),
+            adjusted: Vec::new(),
+            row_offset: options.row_offset,
+            col_offset: options.col_offset,
+            compress: options.compress,
+            compressed: Vec::new(),
+            chunk: options.kitty_chunk,
+            prev: Vec::new(),
+            region: Vec::new(),
+            update: KittyUpdate::Full,
         }
-        let base64_encoded_len = 4 * ceiling_div(len, 3);
+    }
+    fn width(&self) -> u32 {
+        self.width
+    }
+    fn height(&self) -> u32 {
+        self.height
+    }
+    fn create_state(&self) -> Self::State {}
+    fn consume(&mut self, data: &[Pixel]) {
+        let data = if self.color_lut.is_identity() {
+            data
+        } else {
+            self.adjusted.clear();
+            self.adjusted
+                .extend(data.iter().map(|pixel| self.color_lut.apply(*pixel)));
+            &self.adjusted
+        };
+        let width = self.width as usize;
+        self.update = if self.prev.is_empty() {
+            KittyUpdate::Full
+        } else {
+            match dirty_rect(data, &self.prev, width) {
+                None => KittyUpdate::Unchanged,
+                Some(rect) if rect == (0, 0, width, self.height as usize) => KittyUpdate::Full,
+                Some(rect) => KittyUpdate::Region(rect),
+            }
+        };
+        let payload = match self.update {
+            KittyUpdate::Unchanged => &[][..],
+            KittyUpdate::Full => data,
+            KittyUpdate::Region((x, y, w, h)) => {
+                self.region.clear();
+                for row in data[y * width..].chunks(width).take(h) {
+                    self.region.extend_from_slice(&row[x..x + w]);
+                }
+                &self.region
+            }
+        };
+        let ptr = payload.as_ptr().cast::<u8>();
+        let slice = unsafe { from_raw_parts(ptr, payload.len() * 4) };
+        let payload = if self.compress {
+            // flat frames like bad apple shrink massively, which matters more than the time spent compressing
+            self.compressed.clear();
+            let mut encoder = ZlibEncoder::new(&mut self.compressed, Compression::fast());
+            encoder
+                .write_all(slice)
+                .and_then(|_| encoder.finish().map(|_| ()))
+                .expect("writing to memory can't fail");
+            &self.compressed
+        } else {
+            slice
+        };
+        self.encoded.resize(Base64::encoded_len(payload), 0);
+        Base64::encode(payload, &mut self.encoded).unwrap();
+
+        self.prev.clear();
+        self.prev.extend_from_slice(data);
+    }
+    fn invalidate(&mut self, _state: &mut Self::State) {
+        // the next frame is treated like the first
+        self.prev.clear();
+    }
+    fn render_frame(&self, output: &mut impl Write, _state: &mut Self::State) -> io::Result<()> {
+        let id = KITTY_IMAGE_ID;
+        // the control data that goes in the first chunk
+        let control = match self.update {
+            KittyUpdate::Unchanged => return Ok(()),
+            // transmit without displaying. q=2 keeps the terminal from answering each chunk on stdin
+            KittyUpdate::Full => format!("a=t,i={id},s={},v={}", self.width, self.height),
+            // edit the pixels of the image that is already on the screen
+            KittyUpdate::Region((x, y, w, h)) => {
+                format!("a=f,r=1,i={id},x={x},y={y},s={w},v={h}")
+            }
+        };
+        // placements are put at the cursor, so move it back to the top left every frame
+        write!(
+            output,
+            "\x1b[{};{}H",
+            self.row_offset + 1,
+            self.col_offset + 1
+        )?;
+        let mut iter = self.encoded.chunks(self.chunk).peekable();
+        let compression = if self.compress { ",o=z" } else { "" };
+        let mut first = true;
+        while let Some(chunk) = iter.next() {
+            let m = iter.peek().is_some() as u8;
+            // only the first chunk needs the control data
+            if std::mem::take(&mut first) {
+                write!(output, "\x1b_G{control},f=32{compression},q=2,m={m};")?;
+            } else {
+                write!(output, "\x1b_Gm={m};")?;
+            }
+            // base64 is already ascii, so the chunk goes out as is
+            output.write_all(chunk)?;
+            output.write_all(b"\x1b\\")?;
+        }
+        if self.update == KittyUpdate::Full {
+            // swap the last frame's placement for the new one
+            write!(output, "\x1b_Ga=d,d=i,i={id},q=2\x1b\\")?;
+            write!(output, "\x1b_Ga=p,i={id},p=1,C=1,q=2\x1b\\")?;
+        }
+        Ok(())
+    }
+}
+
+/// The number of pixel rows in a sixel band
+const SIXEL_BAND: usize = 6;
+
+/// Renders with sixel graphics, which xterm, mlterm, foot, and friends support.
+/// Sixel images are limited to 256 color registers, so each pixel is quantized to the xterm 256 color palette
+/// rather than building a palette for every frame.
+pub struct SixelRenderer {
+    width: u32,
+    height: u32,
+    // palette index of each pixel
+    indices: Box<[u8]>,
+    color_lut: ChannelLut,
+    row_offset: u32,
+    col_offset: u32,
+}
+
+pub struct SixelState {
+    // the indices of the last frame that was drawn, or `None` before the first one
+    prev: Option<Box<[u8]>>,
+    // one line of sixels per color register, reused between bands
+    planes: Box<[Vec<u8>]>,
+}
+
+impl Renderer for SixelRenderer {
+    type State = SixelState;
+    fn from_dims(width: u32, height: u32, options: &RenderOptions) -> Self {
+        Self {
+            width,
+            height,
+            indices: vec![0; width as usize * height as usize].into_boxed_slice(),
+            color_lut: options.color_lut(BG_GAMMA),
+            row_offset: options.row_offset,
+            col_offset: options.col_offset,
+        }
+    }
+    fn width(&self) -> u32 {
+        self.width
+    }
+    fn height(&self) -> u32 {
+        self.height
+    }
+    fn create_state(&self) -> Self::State {
+        SixelState {
+            prev: None,
+            planes: vec![vec![0; self.width as usize]; 256].into_boxed_slice(),
+        }
+    }
+    fn consume(&mut self, data: &[Pixel]) {
+        for (index, pixel) in self.indices.iter_mut().zip(data) {
+            let [r, g, b, _] = self.color_lut.apply(*pixel);
+            *index = ansi_colours::ansi256_from_rgb([r, g, b]);
+        }
+    }
+    fn invalidate(&mut self, state: &mut Self::State) {
+        state.prev = None;
+    }
+    fn render_frame(&self, output: &mut impl Write, state: &mut Self::State) -> io::Result<()> {
+        let width = self.width as usize;
+        write!(
+            output,
+            "\x1b[{};{}H",
+            self.row_offset + 1,
+            self.col_offset + 1
+        )?;
+        // P2=1 leaves the pixels we don't set alone, so unchanged bands can be skipped entirely
+        write!(output, "\x1bP0;1;0q\"1;1;{};{}", self.width, self.height)?;
+        let mut defined = [false; 256];
+        let mut used = Vec::with_capacity(256);
+        for (band, rows) in self.indices.chunks(width * SIXEL_BAND).enumerate() {
+            if band > 0 {
+                // move down to the next band
+                output.write_all(b"-")?;
+            }
+            let unchanged = state.prev.as_ref().is_some_and(|prev| {
+                let start = band * width * SIXEL_BAND;
+                prev[start..start + rows.len()] == *rows
+            });
+            if !unchanged {
+                used.clear();
+                let mut seen = [false; 256];
+                for (bit, row) in rows.chunks(width).enumerate() {
+                    for (x, &index) in row.iter().enumerate() {
+                        if !seen[index as usize] {
+                            seen[index as usize] = true;
+                            used.push(index);
+                        }
+                        state.planes[index as usize][x] |= 1 << bit;
+                    }
+                }
+                for &index in &used {
+                    if !defined[index as usize] {
+                        defined[index as usize] = true;
+                        // registers take their channels in percent
+                        let (r, g, b) = ansi_colours::rgb_from_ansi256(index);
+                        let [r, g, b] = [r, g, b].map(|c| (c as u32 * 100 + 127) / 255);
+                        write!(output, "#{index};2;{r};{g};{b}")?;
+                    }
+                    write!(output, "#{index}")?;
+                    let plane = &mut state.planes[index as usize];
+                    write_sixels(plane, output)?;
+                    plane.fill(0);
+                    // go back to the start of the band for the next color
+                    output.write_all(b"$")?;
+                }
+            }
+        }
+        output.write_all(b"\x1b\\")?;
+        match &mut state.prev {
+            Some(prev) => prev.copy_from_slice(&self.indices),
+            None => state.prev = Some(self.indices.clone()),
+        }
+        Ok(())
+    }
+}
+
+/// Writes one color's line of sixels, run-length encoding repeats
+fn write_sixels(plane: &[u8], output: &mut impl Write) -> io::Result<()> {
+    // the empty sixels after the last set one don't draw anything
+    let end = plane
+        .iter()
+        .rposition(|&bits| bits != 0)
+        .map_or(0, |i| i + 1);
+    let mut rest = &plane[..end];
+    while let Some(&bits) = rest.first() {
+        let len = rest.iter().take_while(|&&b| b == bits).count();
+        let sixel = b'?' + bits;
+        // the repeat introducer takes at least 3 bytes, so short runs are cheaper written out
+        if len > 3 {
+            write!(output, "!{len}")?;
+            output.write_all(&[sixel])?;
+        } else {
+            output.write_all(&[sixel; 3][..len])?;
+        }
+        rest = &rest[len..];
+    }
+    Ok(())
+}
+
+/// Renders with the iTerm2 inline image protocol, which WezTerm also supports.
+/// The protocol only takes image files, so every frame is encoded as a PNG with the fastest compression.
+/// Unlike kitty, the whole image is sent in one escape sequence, so big frames cost a lot of bandwidth.
+/// Scaling the video down to the terminal's pixel size keeps them in check.
+pub struct ITerm2Renderer {
+    width: u32,
+    height: u32,
+    png: Vec<u8>,
+    encoded: Vec<u8>,
+    color_lut: ChannelLut,
+    // only used when `color_lut` isn't the identity
+    adjusted: Vec<Pixel>,
+    row_offset: u32,
+    col_offset: u32,
+    cells: (u32, u32),
+}
+
+impl Renderer for ITerm2Renderer {
+    type State = ();
+    fn from_dims(width: u32, height: u32, options: &RenderOptions) -> Self {
         Self {
             width,
             height,
-            encoded: String::from_utf8(vec![0u8; base64_encoded_len]).unwrap(),
+            png: Vec::new(),
+            encoded: Vec::new(),
+            color_lut: options.color_lut(BG_GAMMA),
+            adjusted: Vec::new(),
+            row_offset: options.row_offset,
+            col_offset: options.col_offset,
+            cells: options.cells,
         }
     }
     fn width(&self) -> u32 {
@@ -276,22 +1588,233 @@ impl Renderer for KittyRenderer {
     }
     fn create_state(&self) -> Self::State {}
     fn consume(&mut self, data: &[Pixel]) {
+        let data = if self.color_lut.is_identity() {
+            data
+        } else {
+            self.adjusted.clear();
+            self.adjusted
+                .extend(data.iter().map(|pixel| self.color_lut.apply(*pixel)));
+            &self.adjusted
+        };
         let ptr = data.as_ptr().cast::<u8>();
         let slice = unsafe { from_raw_parts(ptr, self.width as usize * self.height as usize * 4) };
-        Base64::encode(slice, unsafe { self.encoded.as_bytes_mut() }).unwrap();
+
+        self.png.clear();
+        let mut encoder = png::Encoder::new(&mut self.png, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_compression(png::Compression::Fast);
+        // writing to memory can't fail, and the dimensions always match the data
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(slice))
+            .expect("failed to encode the frame");
+
+        self.encoded.resize(Base64::encoded_len(&self.png), 0);
+        Base64::encode(&self.png, &mut self.encoded).unwrap();
     }
+    // every frame is sent whole already
+    fn invalidate(&mut self, _state: &mut Self::State) {}
     fn render_frame(&self, output: &mut impl Write, _state: &mut Self::State) -> io::Result<()> {
-        let mut iter = self.encoded.as_bytes().chunks(4096).peekable();
-        while let Some(chunk) = iter.next() {
-            let m = iter.peek().is_some() as u8;
-            // let s = Base64::encode_string(chunk);
-            let s = std::str::from_utf8(chunk).unwrap();
-            write!(
-                output,
-                "\x1b_Ga=T,f=32,s={},v={},C=1,m={},x=1,y=1;{s}\x1b\\",
-                self.width, self.height, m
-            )?;
-        }
-        output.flush()
+        let (columns, rows) = self.cells;
+        write!(
+            output,
+            "\x1b[{};{}H\x1b]1337;File=inline=1;size={};width={columns};height={rows};preserveAspectRatio=1;doNotMoveCursor=1:",
+            self.row_offset + 1,
+            self.col_offset + 1,
+            self.png.len()
+        )?;
+        output.write_all(&self.encoded)?;
+        output.write_all(b"\x07")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Pixel = [0, 0, 0, 0];
+    const WHITE: Pixel = [255, 255, 255, 0];
+    const RED: Pixel = [255, 0, 0, 0];
+
+    /// Draws `prev` and then `current` with truecolor backgrounds `width` pixels wide, returning what `render_frame`
+    /// wrote for the diff between them
+    fn render_with(
+        options: &RenderOptions,
+        width: u32,
+        prev: &[Pixel],
+        current: &[Pixel],
+    ) -> String {
+        let height = current.len() as u32 / width;
+        let mut renderer = DefaultRenderer::<BackgroundRgb>::from_dims(width, height, options);
+        let mut state = renderer.create_state();
+        let mut out = Vec::new();
+        renderer.consume(prev);
+        renderer.render_frame(&mut out, &mut state).unwrap();
+        out.clear();
+        renderer.consume(current);
+        renderer.render_frame(&mut out, &mut state).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn render(width: u32, prev: &[Pixel], current: &[Pixel]) -> String {
+        render_with(&RenderOptions::default(), width, prev, current)
+    }
+
+    #[test]
+    fn single_pixel() {
+        let mut current = [BLACK; 8];
+        current[5] = WHITE;
+        assert_eq!(
+            render(4, &[BLACK; 8], &current),
+            "\x1b[2;2H\x1b[48;2;255;255;255m "
+        );
+        // in a video 2 rows down and 3 columns over
+        let options = RenderOptions {
+            row_offset: 2,
+            col_offset: 3,
+            ..RenderOptions::default()
+        };
+        assert_eq!(
+            render_with(&options, 4, &[BLACK; 8], &current),
+            "\x1b[4;5H\x1b[48;2;255;255;255m "
+        );
+        // a pixel shortly after another in the same color only moves right
+        let mut current = [BLACK; 8];
+        current[0] = WHITE;
+        current[2] = WHITE;
+        assert_eq!(
+            render(4, &[BLACK; 8], &current),
+            "\x1b[1;1H\x1b[48;2;255;255;255m \x1b[C "
+        );
+    }
+
+    #[test]
+    fn full_row() {
+        let mut current = [BLACK; 8];
+        current[4..].fill(WHITE);
+        assert_eq!(
+            render(4, &[BLACK; 8], &current),
+            "\x1b[2;1H\x1b[48;2;255;255;255m    "
+        );
+        // with REP the row is one space and the repeat count
+        let options = RenderOptions {
+            use_rep: true,
+            ..RenderOptions::default()
+        };
+        let mut current = [BLACK; 32];
+        current[16..].fill(WHITE);
+        assert_eq!(
+            render_with(&options, 16, &[BLACK; 32], &current),
+            "\x1b[2;1H\x1b[48;2;255;255;255m \x1b[15b"
+        );
+    }
+
+    #[test]
+    fn stride_wraps() {
+        let mut current = [BLACK; 8];
+        current[2..6].fill(WHITE);
+        assert_eq!(
+            render(4, &[BLACK; 8], &current),
+            // the cursor goes back to the first column of the video instead of relying on a newline
+            "\x1b[1;3H\x1b[48;2;255;255;255m  \x1b[2;1H  "
+        );
+        let options = RenderOptions {
+            row_offset: 1,
+            col_offset: 2,
+            ..RenderOptions::default()
+        };
+        assert_eq!(
+            render_with(&options, 4, &[BLACK; 8], &current),
+            "\x1b[2;5H\x1b[48;2;255;255;255m  \x1b[3;3H  "
+        );
+    }
+
+    #[test]
+    fn color_change_mid_stride() {
+        let mut current = [BLACK; 8];
+        current[1..3].fill(WHITE);
+        current[3] = RED;
+        // the red pixel picks up where the white ones left off, so only the color changes
+        assert_eq!(
+            render(4, &[BLACK; 8], &current),
+            "\x1b[1;2H\x1b[48;2;255;255;255m  \x1b[48;2;255;0;0m "
+        );
+        // unless it starts a row, which the cursor was left waiting to wrap past the end of
+        let mut current = [BLACK; 8];
+        current[2..4].fill(WHITE);
+        current[4] = RED;
+        assert_eq!(
+            render(4, &[BLACK; 8], &current),
+            "\x1b[1;3H\x1b[48;2;255;255;255m  \x1b[2;1H\x1b[48;2;255;0;0m "
+        );
+    }
+
+    #[test]
+    fn cursor_motion() {
+        // prev_end, start, width, offset, and the motion
+        type Case = (usize, usize, usize, (usize, usize), &'static str);
+        let cases: &[Case] = &[
+            // nothing was drawn yet, so the cursor could be anywhere
+            (0, 5, 80, (0, 0), "\x1b[1;6H"),
+            (2, 5, 80, (0, 0), "\x1b[3C"),
+            (2, 70, 80, (0, 0), "\x1b[68C"),
+            (2, 5, 80, (10, 20), "\x1b[3C"),
+            (0, 5, 80, (10, 20), "\x1b[11;26H"),
+            // the last stride ended the row, so the cursor is waiting to wrap
+            (80, 85, 80, (0, 0), "\x1b[2;6H"),
+            (5, 85, 80, (0, 0), "\x1b[B"),
+            (5, 405, 80, (0, 0), "\x1b[5B"),
+            (2150, 2349, 200, (0, 0), "\x1b[B\x1b[D"),
+            // the relative and absolute motions are as long, and a tie goes to the absolute one
+            (5, 87, 80, (0, 0), "\x1b[2;8H"),
+            (105, 204, 100, (0, 0), "\x1b[3;5H"),
+        ];
+        for &(prev_end, start, width, (row_offset, col_offset), motion) in cases {
+            let mut out = Vec::new();
+            move_cursor(prev_end, start, width, (row_offset, col_offset), &mut out).unwrap();
+            assert_eq!(
+                String::from_utf8(out).unwrap(),
+                motion,
+                "from {prev_end} to {start}"
+            );
+            let absolute = format!(
+                "\x1b[{};{}H",
+                start / width + 1 + row_offset,
+                start % width + 1 + col_offset
+            );
+            if motion != absolute {
+                assert!(motion.len() < absolute.len(), "from {prev_end} to {start}");
+            }
+        }
+    }
+
+    #[test]
+    fn image_protocols_start_at_the_offsets() {
+        let options = RenderOptions {
+            row_offset: 2,
+            col_offset: 3,
+            compress: false,
+            ..RenderOptions::default()
+        };
+        let frame = [WHITE; 4];
+        let mut kitty = KittyRenderer::from_dims(2, 2, &options);
+        let mut sixel = SixelRenderer::from_dims(2, 2, &options);
+        let mut iterm2 = ITerm2Renderer::from_dims(2, 2, &options);
+        let mut outs = [Vec::new(), Vec::new(), Vec::new()];
+        kitty.consume(&frame);
+        kitty.render_frame(&mut outs[0], &mut ()).unwrap();
+        sixel.consume(&frame);
+        let mut state = sixel.create_state();
+        sixel.render_frame(&mut outs[1], &mut state).unwrap();
+        iterm2.consume(&frame);
+        iterm2.render_frame(&mut outs[2], &mut ()).unwrap();
+        for out in outs {
+            assert!(
+                out.starts_with(b"\x1b[3;4H"),
+                "{:?}",
+                String::from_utf8_lossy(&out)
+            );
+        }
     }
 }
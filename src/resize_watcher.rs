@@ -0,0 +1,234 @@
+use std::time::{Duration, Instant};
+
+/// Watches the terminal for size changes
+pub trait ResizeWatcher {
+    /// The new size of the terminal as (columns, rows) if it changed since the last call
+    fn resized(&mut self) -> Option<(u16, u16)>;
+}
+
+#[cfg(not(windows))]
+fn terminal_size() -> Option<(u16, u16)> {
+    termsize::get().map(|size| (size.cols, size.rows))
+}
+
+/// Only reports sizes that differ from the last one, since some platforms notify us without an actual change
+fn changed(last: &mut (u16, u16), size: Option<(u16, u16)>) -> Option<(u16, u16)> {
+    let size = size.filter(|size| size != last)?;
+    *last = size;
+    Some(size)
+}
+
+/// Checks the terminal size at most once every `interval`, for platforms without a resize notification
+#[cfg(not(windows))]
+pub struct PollWatcher {
+    last: (u16, u16),
+    interval: Duration,
+    last_check: Instant,
+}
+
+#[cfg(not(windows))]
+impl PollWatcher {
+    pub fn new(size: (u16, u16), interval: Duration) -> Self {
+        Self {
+            last: size,
+            interval,
+            last_check: Instant::now(),
+        }
+    }
+}
+
+#[cfg(not(windows))]
+impl ResizeWatcher for PollWatcher {
+    fn resized(&mut self) -> Option<(u16, u16)> {
+        if self.last_check.elapsed() < self.interval {
+            return None;
+        }
+        self.last_check = Instant::now();
+        changed(&mut self.last, terminal_size())
+    }
+}
+
+/// Holds a resize back until the size has stopped changing for `delay`, so dragging the corner of the terminal
+/// doesn't renegotiate the caps for every size along the way
+pub struct Debounced<W: ResizeWatcher> {
+    inner: W,
+    delay: Duration,
+    // the latest size and when it was seen
+    pending: Option<((u16, u16), Instant)>,
+}
+
+impl<W: ResizeWatcher> Debounced<W> {
+    pub fn new(inner: W, delay: Duration) -> Self {
+        Self {
+            inner,
+            delay,
+            pending: None,
+        }
+    }
+}
+
+impl<W: ResizeWatcher> ResizeWatcher for Debounced<W> {
+    fn resized(&mut self) -> Option<(u16, u16)> {
+        // another resize restarts the wait, so only the final size gets through
+        if let Some(size) = self.inner.resized() {
+            self.pending = Some((size, Instant::now()));
+        }
+        let (size, seen) = self.pending?;
+        if seen.elapsed() < self.delay {
+            return None;
+        }
+        self.pending = None;
+        Some(size)
+    }
+}
+
+/// How long the terminal size has to stay the same before the video follows it
+const DEBOUNCE_DELAY: Duration = Duration::from_millis(150);
+
+#[cfg(unix)]
+mod signal {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::{changed, terminal_size, ResizeWatcher};
+
+    static RESIZED: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn on_sigwinch(_: libc::c_int) {
+        RESIZED.store(true, Ordering::Relaxed);
+    }
+
+    /// Waits for SIGWINCH instead of asking the terminal for its size over and over
+    pub struct SignalWatcher {
+        last: (u16, u16),
+    }
+
+    impl SignalWatcher {
+        /// Installs the SIGWINCH handler, returning `None` if that fails
+        pub fn new(size: (u16, u16)) -> Option<Self> {
+            // SAFETY: the handler only touches an atomic, which is async-signal-safe
+            let previous = unsafe {
+                libc::signal(
+                    libc::SIGWINCH,
+                    on_sigwinch as extern "C" fn(libc::c_int) as libc::sighandler_t,
+                )
+            };
+            (previous != libc::SIG_ERR).then_some(Self { last: size })
+        }
+    }
+
+    impl ResizeWatcher for SignalWatcher {
+        fn resized(&mut self) -> Option<(u16, u16)> {
+            if !RESIZED.swap(false, Ordering::Relaxed) {
+                return None;
+            }
+            changed(&mut self.last, terminal_size())
+        }
+    }
+}
+
+#[cfg(windows)]
+mod console {
+    use windows_sys::Win32::System::Console::{
+        GetConsoleScreenBufferInfo, GetStdHandle, CONSOLE_SCREEN_BUFFER_INFO, STD_OUTPUT_HANDLE,
+    };
+
+    use super::{changed, ResizeWatcher};
+
+    /// Asks the console for the size of its window, which is cheap enough to do on every check
+    pub struct ConsoleWatcher {
+        last: (u16, u16),
+    }
+
+    impl ConsoleWatcher {
+        pub fn new(size: (u16, u16)) -> Self {
+            Self { last: size }
+        }
+    }
+
+    fn window_size() -> Option<(u16, u16)> {
+        // SAFETY: the info struct is plain old data that the call fills in
+        unsafe {
+            let handle = GetStdHandle(STD_OUTPUT_HANDLE);
+            let mut info: CONSOLE_SCREEN_BUFFER_INFO = std::mem::zeroed();
+            if GetConsoleScreenBufferInfo(handle, &mut info) == 0 {
+                return None;
+            }
+            // the window is a view into the (usually much taller) screen buffer
+            let window = info.srWindow;
+            let columns = window.Right - window.Left + 1;
+            let rows = window.Bottom - window.Top + 1;
+            Some((columns as u16, rows as u16))
+        }
+    }
+
+    impl ResizeWatcher for ConsoleWatcher {
+        fn resized(&mut self) -> Option<(u16, u16)> {
+            changed(&mut self.last, window_size())
+        }
+    }
+}
+
+/// How often the fallback watcher asks for the terminal size by default, which keeps the ioctl out of most frames
+#[cfg(not(windows))]
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[cfg(unix)]
+pub use signal::SignalWatcher;
+
+#[cfg(windows)]
+pub use console::ConsoleWatcher;
+
+/// The watchers [`default_watcher`] picks between, so it can hand back one type whichever it went with
+pub enum PlatformWatcher {
+    #[cfg(unix)]
+    Signal(SignalWatcher),
+    #[cfg(windows)]
+    Console(ConsoleWatcher),
+    #[cfg(not(windows))]
+    Poll(PollWatcher),
+}
+
+impl ResizeWatcher for PlatformWatcher {
+    fn resized(&mut self) -> Option<(u16, u16)> {
+        match self {
+            #[cfg(unix)]
+            Self::Signal(watcher) => watcher.resized(),
+            #[cfg(windows)]
+            Self::Console(watcher) => watcher.resized(),
+            #[cfg(not(windows))]
+            Self::Poll(watcher) => watcher.resized(),
+        }
+    }
+}
+
+/// The watcher from [`default_watcher`]
+pub type DefaultWatcher = Debounced<PlatformWatcher>;
+
+/// The best watcher for this platform, starting from the terminal size the video was set up with
+#[cfg(not(windows))]
+pub fn default_watcher(size: (u16, u16)) -> DefaultWatcher {
+    default_watcher_with_interval(size, DEFAULT_POLL_INTERVAL)
+}
+
+/// The best watcher for this platform, starting from the terminal size the video was set up with
+#[cfg(windows)]
+pub fn default_watcher(size: (u16, u16)) -> DefaultWatcher {
+    Debounced::new(
+        PlatformWatcher::Console(ConsoleWatcher::new(size)),
+        DEBOUNCE_DELAY,
+    )
+}
+
+/// Like [`default_watcher`], but asking for the terminal size every `poll_interval` when it has to poll.
+/// On unix that's only when the SIGWINCH handler can't be installed, like in some sandboxes
+#[cfg(not(windows))]
+pub fn default_watcher_with_interval(size: (u16, u16), poll_interval: Duration) -> DefaultWatcher {
+    #[cfg(unix)]
+    let watcher = match SignalWatcher::new(size) {
+        Some(watcher) => PlatformWatcher::Signal(watcher),
+        None => PlatformWatcher::Poll(PollWatcher::new(size, poll_interval)),
+    };
+    #[cfg(not(unix))]
+    let watcher = PlatformWatcher::Poll(PollWatcher::new(size, poll_interval));
+    Debounced::new(watcher, DEBOUNCE_DELAY)
+}
@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+/// A subtitle and when it is on the screen, in the time of the video
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cue {
+    pub start: Duration,
+    /// When the subtitle goes away, or `None` if it stays until the next one
+    pub end: Option<Duration>,
+    pub text: String,
+}
+
+impl Cue {
+    /// Whether the cue is on the screen at `position`
+    pub fn is_active(&self, position: Duration) -> bool {
+        self.start <= position && self.end.is_none_or(|end| position < end)
+    }
+}
+
+/// Where the subtitles come from
+#[derive(Debug, Clone)]
+pub enum Subtitles {
+    /// The subtitle track in the video that the producer was told to show
+    Embedded,
+    /// Cues from a subtitle file, which are timed against the position of the video
+    Cues(Vec<Cue>),
+}
+
+/// The text of every cue on the screen at `position`, one after the other when they overlap
+pub fn active_text(cues: &[Cue], position: Duration) -> Option<String> {
+    let active: Vec<&str> = cues
+        .iter()
+        .filter(|cue| cue.is_active(position))
+        .map(|cue| cue.text.as_str())
+        .collect();
+    (!active.is_empty()).then(|| active.join("\n"))
+}
+
+/// Parses a SubRip timestamp like `01:02:03,456`. Some files use a period before the milliseconds instead
+fn parse_timestamp(s: &str) -> Option<Duration> {
+    let (time, millis) = s.trim().split_once([',', '.'])?;
+    let mut fields = time.split(':').map(|field| field.parse::<u64>().ok());
+    let (hours, minutes, seconds) = (fields.next()??, fields.next()??, fields.next()??);
+    // the fraction is usually three digits, but not always
+    let digits = millis.get(..millis.len().min(3))?;
+    let millis = digits.parse::<u64>().ok()? * 10u64.pow(3 - digits.len() as u32);
+    Some(Duration::from_millis(
+        ((hours * 60 + minutes) * 60 + seconds) * 1000 + millis,
+    ))
+}
+
+/// Parses the cues of a SubRip (.srt) file, sorted by when they start. Cues that don't parse are skipped, since
+/// subtitle files in the wild are rarely perfect
+pub fn parse_srt(text: &str) -> Vec<Cue> {
+    let text = text.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+    let mut cues: Vec<Cue> = text
+        .split("\n\n")
+        .filter_map(|block| {
+            let mut lines = block.trim_matches('\n').lines();
+            let mut timing = lines.next()?;
+            // the number of the cue is optional as far as we care
+            if !timing.contains("-->") {
+                timing = lines.next()?;
+            }
+            let (start, end) = timing.split_once("-->")?;
+            // players put positions after the end time, which we ignore
+            let end = end.split_whitespace().next()?;
+            let text = strip_tags(&lines.collect::<Vec<_>>().join("\n"));
+            Some(Cue {
+                start: parse_timestamp(start)?,
+                end: Some(parse_timestamp(end)?),
+                text,
+            })
+        })
+        .collect();
+    cues.sort_by_key(|cue| cue.start);
+    cues
+}
+
+/// Removes the styling from subtitle text, like the `<i>` tags in SubRip and pango markup and the `{\an8}` overrides
+/// carried over from SubStation Alpha
+pub fn strip_tags(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        // a lone < or { is left alone, since it's just part of the text
+        let end = match (c, chars.peek()) {
+            ('<', Some(next)) if next.is_ascii_alphabetic() || *next == '/' => '>',
+            ('{', Some('\\')) => '}',
+            _ => {
+                stripped.push(c);
+                continue;
+            }
+        };
+        chars.by_ref().find(|c| *c == end);
+    }
+    stripped
+}
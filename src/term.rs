@@ -0,0 +1,303 @@
+use std::io::{self, Write};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub const BEGIN_SYNC: &[u8] = b"\x1b[?2026h";
+pub const END_SYNC: &[u8] = b"\x1b[?2026l";
+
+fn hide_cursor(mut out: impl Write) -> io::Result<()> {
+    out.write_all(b"\x1b[?25l")?;
+    Ok(())
+}
+fn show_cursor(mut out: impl Write) -> io::Result<()> {
+    out.write_all(b"\x1b[?25h")?;
+    Ok(())
+}
+
+/// A wrapper around a `Write` that hides the cursor on creation and shows it again on drop
+pub struct HideCursor<W: Write>(W, bool);
+impl<W: Write> HideCursor<W> {
+    /// Only hides the cursor if `enabled`, like [`AltScreen::new`]
+    pub fn new(mut writer: W, enabled: bool) -> Self {
+        let hidden = enabled && hide_cursor(&mut writer).is_ok();
+        Self(writer, hidden)
+    }
+    pub fn show(&mut self) -> io::Result<()> {
+        if std::mem::take(&mut self.1) {
+            show_cursor(&mut self.0)?;
+        }
+        Ok(())
+    }
+}
+impl<W: Write> Drop for HideCursor<W> {
+    fn drop(&mut self) {
+        let _ = self.show();
+    }
+}
+impl<W: Write> Deref for HideCursor<W> {
+    type Target = W;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<W: Write> DerefMut for HideCursor<W> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// A wrapper around a `Write` that switches to the alternate screen on creation and back on drop or `leave`
+pub struct AltScreen<W: Write> {
+    inner: W,
+    active: bool,
+}
+impl<W: Write> AltScreen<W> {
+    /// Only switches to the alternate screen if `enabled`, so callers don't need a separate type for the old behavior
+    pub fn new(mut inner: W, enabled: bool) -> Self {
+        let active = enabled && inner.write_all(b"\x1b[?1049h").is_ok();
+        Self { inner, active }
+    }
+    /// Switches back to the normal screen if we haven't already
+    pub fn leave(&mut self) -> io::Result<()> {
+        if self.active {
+            self.active = false;
+            self.inner.write_all(b"\x1b[?1049l")?;
+        }
+        Ok(())
+    }
+}
+impl<W: Write> Drop for AltScreen<W> {
+    fn drop(&mut self) {
+        let _ = self.leave();
+    }
+}
+impl<W: Write> Write for AltScreen<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.inner.write_all(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A wrapper around a `Write` that turns on mouse reporting on creation and off again on drop, so clicks come in on
+/// stdin as SGR mouse escapes that [`Keys`] understands
+pub struct MouseReporting<W: Write> {
+    inner: W,
+    active: bool,
+}
+impl<W: Write> MouseReporting<W> {
+    /// Only turns on mouse reporting if `enabled`, since it keeps the terminal from selecting text with the mouse
+    pub fn new(mut inner: W, enabled: bool) -> Self {
+        let active = enabled && inner.write_all(b"\x1b[?1000h\x1b[?1006h").is_ok();
+        Self { inner, active }
+    }
+}
+impl<W: Write> Drop for MouseReporting<W> {
+    fn drop(&mut self) {
+        if self.active {
+            let _ = self.inner.write_all(b"\x1b[?1006l\x1b[?1000l");
+            let _ = self.inner.flush();
+        }
+    }
+}
+impl<W: Write> Write for MouseReporting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.inner.write_all(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The size of the terminal's text area in pixels, if the terminal reports it
+#[cfg(unix)]
+pub fn pixel_size() -> Option<(u16, u16)> {
+    // SAFETY: winsize is plain old data that TIOCGWINSZ fills in
+    let size = unsafe {
+        let mut size: libc::winsize = std::mem::zeroed();
+        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) != 0 {
+            return None;
+        }
+        size
+    };
+    (size.ws_xpixel != 0 && size.ws_ypixel != 0).then_some((size.ws_xpixel, size.ws_ypixel))
+}
+
+/// The size of the terminal's text area in pixels, if the terminal reports it
+#[cfg(not(unix))]
+pub fn pixel_size() -> Option<(u16, u16)> {
+    None
+}
+
+/// Guesses whether the terminal supports synchronized output (mode 2026) from the environment.
+/// Terminals that don't support it ignore the escape anyway, so a wrong guess is harmless.
+pub fn supports_synchronized_output() -> bool {
+    let term = std::env::var("TERM").unwrap_or_default();
+    let term_program = std::env::var("TERM_PROGRAM").unwrap_or_default();
+    [
+        "kitty",
+        "foot",
+        "alacritty",
+        "contour",
+        "ghostty",
+        "wezterm",
+    ]
+    .iter()
+    .any(|name| term.contains(name))
+        || ["WezTerm", "iTerm.app", "ghostty", "vscode"].contains(&term_program.as_str())
+}
+
+/// The flag from [`interrupt_flag`], once its handlers are installed
+static INTERRUPT: Mutex<Option<Arc<AtomicBool>>> = Mutex::new(None);
+
+/// A flag that is set on ctrl+c, and on unix also when the process is asked to terminate or the terminal hangs up,
+/// so the loop that checks it can stop and the terminal gets restored on the way out.
+///
+/// The handlers are installed by the first call, and every call after it gets the same flag, since ctrl+c can only
+/// have one handler per process
+pub fn interrupt_flag() -> Result<Arc<AtomicBool>, Box<dyn std::error::Error>> {
+    let mut installed = INTERRUPT.lock().unwrap();
+    if let Some(flag) = &*installed {
+        return Ok(flag.clone());
+    }
+    let flag = Arc::new(AtomicBool::new(false));
+    #[cfg(unix)]
+    for signal in [signal_hook::consts::SIGTERM, signal_hook::consts::SIGHUP] {
+        signal_hook::flag::register(signal, flag.clone())?;
+    }
+    let handler_flag = flag.clone();
+    ctrlc::set_handler(move || handler_flag.store(true, Ordering::Relaxed))?;
+    *installed = Some(flag.clone());
+    Ok(flag)
+}
+
+/// Something the user did in the terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    Key(u8),
+    /// A press of the left mouse button at a 1-based `(column, row)`
+    Click(u16, u16),
+    Up,
+    Down,
+}
+
+/// How long to wait for the rest of an escape sequence, which the terminal sends all at once
+const ESCAPE_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// The longest escape sequence worth reading, so garbage on stdin can't grow it forever
+const MAX_ESCAPE_LEN: usize = 32;
+
+/// Turns the parameters and final byte of a CSI escape sequence into an input, or `None` for the ones we don't use
+fn parse_csi(params: &[u8], last: u8) -> Option<Input> {
+    match (params, last) {
+        // SGR mouse reports are <button;column;row, ending in M on press and m on release
+        ([b'<', params @ ..], b'M') => {
+            let params = std::str::from_utf8(params).ok()?;
+            let mut fields = params.split(';').map(|field| field.parse::<u16>().ok());
+            match (fields.next()??, fields.next()??, fields.next()??) {
+                (0, column, row) => Some(Input::Click(column, row)),
+                _ => None,
+            }
+        }
+        (b"", b'A') => Some(Input::Up),
+        (b"", b'B') => Some(Input::Down),
+        _ => None,
+    }
+}
+
+/// Reads keypresses from the terminal on a separate thread without waiting for enter.
+/// The terminal's previous settings are restored on drop.
+pub struct Keys {
+    recv: Receiver<u8>,
+    #[cfg(unix)]
+    original: libc::termios,
+}
+impl Keys {
+    /// Puts the terminal into cbreak mode, returning `None` when stdin isn't a terminal
+    #[cfg(unix)]
+    pub fn start() -> Option<Self> {
+        use std::io::Read;
+        // SAFETY: termios is plain old data, and it is only used after tcgetattr fills it in
+        let original = unsafe {
+            if libc::isatty(libc::STDIN_FILENO) != 1 {
+                return None;
+            }
+            let mut original = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                return None;
+            }
+            // keep ISIG so ctrl+c still interrupts
+            let mut cbreak = original;
+            cbreak.c_lflag &= !(libc::ICANON | libc::ECHO);
+            cbreak.c_cc[libc::VMIN] = 1;
+            cbreak.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &cbreak) != 0 {
+                return None;
+            }
+            original
+        };
+        let (send, recv) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            for byte in io::stdin().lock().bytes() {
+                match byte {
+                    Ok(byte) if send.send(byte).is_ok() => (),
+                    _ => break,
+                }
+            }
+        });
+        Some(Self { recv, original })
+    }
+    /// Keybindings are only supported on unix for now
+    #[cfg(not(unix))]
+    pub fn start() -> Option<Self> {
+        None
+    }
+    /// The next key that was pressed or click that was made, if there is one
+    pub fn poll(&self) -> Option<Input> {
+        loop {
+            let byte = self.recv.try_recv().ok()?;
+            if byte != 0x1b {
+                return Some(Input::Key(byte));
+            }
+            let next = || self.recv.recv_timeout(ESCAPE_TIMEOUT).ok();
+            match next() {
+                Some(b'[') => (),
+                // alt and a key, which nothing is bound to
+                Some(_) => continue,
+                None => return Some(Input::Key(byte)),
+            }
+            let mut params = Vec::new();
+            // the parameters run until the final byte, which is a letter or one of a few symbols
+            let last = loop {
+                match next() {
+                    Some(byte @ 0x40..=0x7e) => break Some(byte),
+                    Some(byte) if params.len() < MAX_ESCAPE_LEN => params.push(byte),
+                    _ => break None,
+                }
+            };
+            if let Some(input) = last.and_then(|last| parse_csi(&params, last)) {
+                return Some(input);
+            }
+        }
+    }
+}
+#[cfg(unix)]
+impl Drop for Keys {
+    fn drop(&mut self) {
+        // SAFETY: `original` came from tcgetattr
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use onna::producer::{Decoder, ProducerOptions, Repeat, Source};
+use onna::render::RenderOptions;
+use onna::{Colors, Mode, PlayConfig, Scale};
+
+/// A config that plays `source` by drawing truecolor backgrounds into a terminal of `term_size` and nothing else, so
+/// the output is only the frames
+pub fn config(source: Source, term_size: (u16, u16)) -> PlayConfig {
+    PlayConfig {
+        source,
+        producer: ProducerOptions {
+            timeout: Duration::from_secs(1),
+            decoder: Decoder::Auto,
+            repeat: Repeat::Never,
+            verbose: false,
+            start_at: None,
+            rate: 1.,
+            buffer: 2,
+            buffer_duration: None,
+            rtsp_latency: None,
+            rtsp_tcp: false,
+            subtitles: None,
+            max_bad_samples: 0,
+        },
+        render: RenderOptions::default(),
+        mode: Mode::Cells {
+            colors: Colors::Truecolor,
+            grayscale: false,
+            background: true,
+        },
+        no_convert: false,
+        sync: false,
+        stats: false,
+        progress: false,
+        subtitles: None,
+        banner: None,
+        scale: Scale::Fill,
+        pad_color: None,
+        scale_method: None,
+        squash: false,
+        stall_timeout: None,
+        max_fps: None,
+        pace: false,
+        batch: false,
+        term_size,
+        cell_aspect: (1, 2),
+        cell_px: None,
+        interactive: false,
+        follow_resize: false,
+        single_frame: false,
+        flush_interval: None,
+        profile: false,
+        dump_frame: None,
+    }
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use onna::mock::{MockEvent, MockProducer};
+use onna::producer::Source;
+use onna::Player;
+
+const WHITE: [u8; 4] = [255, 255, 255, 0];
+const BLACK: [u8; 4] = [0, 0, 0, 0];
+
+fn frame(pixels: &[[u8; 4]]) -> Vec<u8> {
+    pixels.concat()
+}
+
+fn play(term_size: (u16, u16), script: Vec<MockEvent>) -> String {
+    let mut player = Player::with_producer(
+        common::config(Source::Live(String::new()), term_size),
+        MockProducer::new(script),
+    );
+    let mut out = Vec::new();
+    player.run(&mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn checkerboard() {
+    let checkerboard = frame(&[WHITE, BLACK, BLACK, WHITE]);
+    let out = play(
+        (2, 2),
+        vec![
+            MockEvent::Resize {
+                width: 2,
+                height: 2,
+            },
+            MockEvent::Frame {
+                data: checkerboard.clone(),
+                pts: None,
+            },
+            // nothing changed, so nothing is drawn
+            MockEvent::Frame {
+                data: checkerboard,
+                pts: None,
+            },
+            MockEvent::Frame {
+                data: frame(&[BLACK, BLACK, BLACK, WHITE]),
+                pts: None,
+            },
+        ],
+    );
+    assert_eq!(
+        out,
+        concat!(
+            "\x1b[2J",
+            // the first frame comes out in full, with the two black pixels as one stride across the rows
+            "\x1b[1;1H\x1b[48;2;255;255;255m \x1b[48;2;0;0;0m \x1b[2;1H \x1b[48;2;255;255;255m ",
+            // only the pixel that changed in the last one
+            "\x1b[1;1H\x1b[48;2;0;0;0m ",
+        )
+    );
+}
+
+#[test]
+fn resize() {
+    let out = play(
+        (3, 2),
+        vec![
+            MockEvent::Resize {
+                width: 2,
+                height: 2,
+            },
+            MockEvent::Frame {
+                data: frame(&[WHITE, WHITE, WHITE, WHITE]),
+                pts: None,
+            },
+            MockEvent::Resize {
+                width: 3,
+                height: 1,
+            },
+            MockEvent::Frame {
+                data: frame(&[WHITE, WHITE, BLACK]),
+                pts: None,
+            },
+        ],
+    );
+    assert_eq!(
+        out,
+        concat!(
+            "\x1b[2J\x1b[1;1H\x1b[48;2;255;255;255m  \x1b[2;1H  ",
+            // the row under the new frame is cleared to the terminal's background, and the frame at the new size comes
+            // out in full
+            "\x1b[0m\x1b[2;1H\x1b[2K",
+            "\x1b[1;1H\x1b[48;2;255;255;255m  \x1b[48;2;0;0;0m ",
+        )
+    );
+}
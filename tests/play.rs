@@ -0,0 +1,44 @@
+mod common;
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use onna::producer::{Repeat, Source};
+use onna::{play, Player};
+
+#[test]
+fn generated_video() {
+    // three frames of the same white picture, which is drawn once and then never changes
+    let source = Source::Live("videotestsrc num-buffers=3 pattern=white".to_owned());
+    let mut out = Vec::new();
+    play(common::config(source, (4, 2)), &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("\x1b[2J\x1b[1;1H\x1b[48;2;"), "{out:?}");
+    assert_eq!(out.matches("\x1b[1;1H").count(), 1, "{out:?}");
+    assert!(out.ends_with("    \x1b[2;1H    "), "{out:?}");
+}
+
+#[test]
+fn gif_frames() {
+    // a red, a green, and a blue frame that are 100ms each
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/gif/rgb.gif");
+    let source = Source::Playbin {
+        playlist: vec![format!("file://{}", path.display())],
+        filter: None,
+    };
+    let mut config = common::config(source, (2, 1));
+    config.pace = true;
+    // like --loop-count 2
+    config.producer.repeat = Repeat::Times(1);
+    let mut player = Player::new(config).unwrap();
+    let start = Instant::now();
+    let mut out = Vec::new();
+    player.run(&mut out).unwrap();
+    let elapsed = start.elapsed();
+    let out = String::from_utf8(out).unwrap();
+    // every frame is a different color from the one before it, so each one is drawn, both times through
+    assert_eq!(out.matches("\x1b[1;1H").count(), 6, "{out:?}");
+    // and each is shown for its delay instead of as fast as they're decoded, leaving at least the first two of each
+    // time through, since the video can end as soon as the last one is drawn
+    assert!(elapsed >= Duration::from_millis(400), "{elapsed:?}");
+}